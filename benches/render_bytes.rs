@@ -0,0 +1,166 @@
+//! Measure the bytes `Screen::present` writes for a colorful UI, compared with the naive output
+//! that resets the attributes on every change, moves the cursor with absolute positions and
+//! writes every cell of uniform runs.
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tuikit::attr::{Attr, Color, ColorDepth, Effect};
+use tuikit::canvas::Canvas;
+use tuikit::output::{Command, Output};
+use tuikit::screen::Screen;
+
+const WIDTH: usize = 120;
+const HEIGHT: usize = 40;
+
+/// counts the bytes written to it
+struct Counter(Arc<AtomicUsize>);
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.fetch_add(buf.len(), Ordering::Relaxed);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn count_bytes(commands: Vec<Command>) -> usize {
+    let count = Arc::new(AtomicUsize::new(0));
+    let mut output = Output::headless(Box::new(Counter(count.clone())), WIDTH, HEIGHT);
+    output.set_color_depth(ColorDepth::TrueColor);
+    for command in commands {
+        output.execute(command);
+    }
+    output.flush();
+    count.load(Ordering::Relaxed)
+}
+
+/// the commands without minimal attribute transitions and relative cursor moves
+fn naive(commands: &[Command]) -> Vec<Command> {
+    let (mut row, mut col) = (0, 0);
+    let mut result = Vec::new();
+    for command in commands.iter().cloned() {
+        match command {
+            Command::CursorGoto { row: r, col: c } => {
+                row = r;
+                col = c;
+                result.push(command);
+            }
+            Command::CursorRight(amount) => {
+                col += amount;
+                result.push(Command::CursorGoto { row, col });
+            }
+            Command::CursorNextLine => {
+                row += 1;
+                col = 0;
+                result.push(Command::CursorGoto { row, col });
+            }
+            Command::PutChar(_) => {
+                col += 1;
+                result.push(command);
+            }
+            Command::RepeatChar(ch, count) => {
+                col += count;
+                result.extend((0..count).map(|_| Command::PutChar(ch)));
+            }
+            Command::EraseChars(count) => {
+                result.extend((0..count).map(|_| Command::PutChar(' ')));
+                result.push(Command::CursorGoto { row, col });
+            }
+            Command::ChangeAttribute { to, .. } => {
+                result.push(Command::ResetAttributes);
+                result.push(Command::SetAttribute(to));
+            }
+            command => result.push(command),
+        }
+    }
+    result
+}
+
+/// a syntax highlighted listing, `offset` is the first line shown
+fn draw_listing(screen: &mut Screen, offset: usize) {
+    let palette = [
+        Attr::default()
+            .fg(Color::Rgb(198, 120, 221))
+            .effect(Effect::BOLD),
+        Attr::default().fg(Color::Rgb(152, 195, 121)),
+        Attr::default().fg(Color::Rgb(97, 175, 239)),
+        Attr::default()
+            .fg(Color::AnsiValue(244))
+            .effect(Effect::ITALIC),
+        Attr::default(),
+    ];
+    let _ = screen.clear();
+    for row in 0..HEIGHT {
+        let line = row + offset;
+        let number = format!("{:>4} ", line);
+        let _ = screen.print_with_attr(row, 0, &number, Attr::default().fg(Color::LIGHT_BLACK));
+
+        let mut col = number.len();
+        for word in 0..(line % 7 + 6) {
+            let attr = palette[(line * 3 + word) % palette.len()];
+            let text = &"let mut value = render(screen);"[..(line + word) % 8 + 3];
+            if col + text.len() >= WIDTH {
+                break;
+            }
+            col += screen.print_with_attr(row, col, text, attr).unwrap() + 1;
+        }
+    }
+}
+
+/// panes with borders and colored backgrounds
+fn draw_panes(screen: &mut Screen) {
+    let border = Attr::default().fg(Color::LIGHT_BLACK);
+    let panes = [
+        (0, 0, 40, HEIGHT, Color::Rgb(40, 44, 52)),
+        (0, 40, 80, HEIGHT / 2, Color::Rgb(33, 37, 43)),
+        (HEIGHT / 2, 40, 80, HEIGHT / 2, Color::Default),
+    ];
+    let _ = screen.clear();
+    for &(top, left, width, height, bg) in panes.iter() {
+        let horizontal = "─".repeat(width - 2);
+        let blank = " ".repeat(width - 2);
+        let _ = screen.print_with_attr(top, left, &format!("┌{}┐", horizontal), border);
+        for row in top + 1..top + height - 1 {
+            let _ = screen.print_with_attr(row, left, "│", border);
+            let _ = screen.print_with_attr(row, left + 1, &blank, Attr::default().bg(bg));
+            let _ = screen.print_with_attr(row, left + width - 1, "│", border);
+        }
+        let bottom = format!("└{}┘", horizontal);
+        let _ = screen.print_with_attr(top + height - 1, left, &bottom, border);
+        let _ = screen.print_with_attr(top + 1, left + 2, "title", Attr::default().bg(bg));
+    }
+}
+
+fn report(name: &str, commands: Vec<Command>) {
+    let naive_bytes = count_bytes(naive(&commands));
+    let bytes = count_bytes(commands);
+    println!(
+        "{:<24} {:>8} bytes, naive {:>8} bytes, saved {:>5.1}%",
+        name,
+        bytes,
+        naive_bytes,
+        100.0 * (naive_bytes - bytes) as f64 / naive_bytes as f64
+    );
+}
+
+fn main() {
+    let mut screen = Screen::new(WIDTH, HEIGHT);
+    draw_listing(&mut screen, 0);
+    report("full frame", screen.present());
+
+    draw_listing(&mut screen, 1);
+    report("scrolled by a line", screen.present());
+
+    let _ = screen.print_with_attr(20, 30, "cursor", Attr::default().bg(Color::BLUE));
+    report("partial update", screen.present());
+
+    let mut screen = Screen::new(WIDTH, HEIGHT);
+    screen.use_repeat(true);
+    screen.use_erase_chars(true);
+    draw_panes(&mut screen);
+    report("panes with REP and ECH", screen.present());
+}
@@ -2,20 +2,26 @@
 
 use bitflags::bitflags;
 
-pub use crate::color::Color;
+pub use crate::color::{Color, ColorDepth};
 
-/// `Attr` is a rendering attribute that contains fg color, bg color and text effect.
+/// `Attr` is a rendering attribute that contains fg color, bg color, text effect and the color of
+/// underlines.
 ///
 /// ```
 /// use tuikit::attr::{Attr, Effect, Color};
 ///
 /// let attr = Attr { fg: Color::RED, effect: Effect::BOLD, ..Attr::default() };
+/// let squiggly = Attr::default()
+///     .effect(Effect::CURLY_UNDERLINE)
+///     .underline_color(Color::Rgb(255, 0, 0));
 /// ```
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Attr {
     pub fg: Color,
     pub bg: Color,
     pub effect: Effect,
+    /// the color of underlines, `Color::Default` to use the foreground color
+    pub underline_color: Color,
 }
 
 impl Default for Attr {
@@ -24,6 +30,7 @@ impl Default for Attr {
             fg: Color::default(),
             bg: Color::default(),
             effect: Effect::empty(),
+            underline_color: Color::default(),
         }
     }
 }
@@ -33,8 +40,8 @@ impl Attr {
     /// ```
     /// use tuikit::attr::{Attr, Color, Effect};
     ///
-    /// let default = Attr{fg: Color::BLUE, bg: Color::YELLOW, effect: Effect::BOLD};
-    /// let new = Attr{fg: Color::Default, bg: Color::WHITE, effect: Effect::REVERSE};
+    /// let default = Attr{fg: Color::BLUE, bg: Color::YELLOW, effect: Effect::BOLD, ..Attr::default()};
+    /// let new = Attr{fg: Color::Default, bg: Color::WHITE, effect: Effect::REVERSE, ..Attr::default()};
     /// let extended = default.extend(new);
     ///
     /// assert_eq!(Color::BLUE, extended.fg);
@@ -54,6 +61,11 @@ impl Attr {
                 self.bg
             },
             effect: self.effect | new_attr.effect,
+            underline_color: if new_attr.underline_color != Color::default() {
+                new_attr.underline_color
+            } else {
+                self.underline_color
+            },
         }
     }
 
@@ -71,16 +83,32 @@ impl Attr {
         self.effect = effect;
         self
     }
+
+    pub fn underline_color(mut self, color: Color) -> Self {
+        self.underline_color = color;
+        self
+    }
 }
 
 bitflags! {
     /// `Effect` is the effect of a text
-    pub struct Effect: u8 {
-        const BOLD = 0b00000001;
-        const DIM = 0b00000010;
-        const UNDERLINE = 0b00000100;
-        const BLINK = 0b00001000;
-        const REVERSE = 0b00010000;
+    ///
+    /// The extended underline styles (double, curly, dotted, dashed) are not supported by every
+    /// terminal, those that don't understand them normally fall back to a single underline.
+    pub struct Effect: u16 {
+        const BOLD = 0b00000000_00000001;
+        const DIM = 0b00000000_00000010;
+        const UNDERLINE = 0b00000000_00000100;
+        const BLINK = 0b00000000_00001000;
+        const REVERSE = 0b00000000_00010000;
+        const ITALIC = 0b00000000_00100000;
+        const STRIKETHROUGH = 0b00000000_01000000;
+        const HIDDEN = 0b00000000_10000000;
+        const OVERLINE = 0b00000001_00000000;
+        const DOUBLE_UNDERLINE = 0b00000010_00000000;
+        const CURLY_UNDERLINE = 0b00000100_00000000;
+        const DOTTED_UNDERLINE = 0b00001000_00000000;
+        const DASHED_UNDERLINE = 0b00010000_00000000;
     }
 }
 
@@ -0,0 +1,65 @@
+//! Minimal base64 (RFC 4648, standard alphabet with padding) used by OSC 52
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len() / 3 * 4 + 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let bits = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (bits >> (18 - 6 * i)) & 0b111111;
+                encoded.push(ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// decode base64 data, return `None` if the data is malformed
+pub fn decode(data: &str) -> Option<Vec<u8>> {
+    let data = data.trim_end_matches('=').as_bytes();
+    let mut decoded = Vec::with_capacity(data.len() * 3 / 4);
+    for chunk in data.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut bits = 0u32;
+        for (i, &byte) in chunk.iter().enumerate() {
+            let value = ALPHABET.iter().position(|&b| b == byte)? as u32;
+            bits |= value << (18 - 6 * i);
+        }
+        let bytes = [(bits >> 16) as u8, (bits >> 8) as u8, bits as u8];
+        decoded.extend_from_slice(&bytes[..chunk.len() - 1]);
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        let cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("hello, 世界", "aGVsbG8sIOS4lueVjA=="),
+        ];
+        for &(data, encoded) in cases.iter() {
+            assert_eq!(encoded, encode(data.as_bytes()));
+            assert_eq!(Some(data.as_bytes().to_vec()), decode(encoded));
+        }
+        assert_eq!(None, decode("Z"));
+        assert_eq!(None, decode("Zm9v!"));
+    }
+}
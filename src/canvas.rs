@@ -1,8 +1,9 @@
 ///! A canvas is a trait defining the draw actions
 use crate::attr::Attr;
-use crate::cell::Cell;
+use crate::cell::{Cell, Hyperlink};
+use crate::output::CursorStyle;
 use crate::Result;
-use unicode_width::UnicodeWidthChar;
+use unicode_segmentation::UnicodeSegmentation;
 
 pub trait Canvas {
     /// Get the canvas size (width, height)
@@ -25,12 +26,13 @@ pub trait Canvas {
         ch: char,
         attr: Attr,
     ) -> Result<usize> {
-        self.put_cell(row, col, Cell { ch, attr })
+        self.put_cell(row, col, Cell::from(ch).attribute(attr))
     }
 
     /// print `content` starting with position `(row, col)` with `attr`
     /// - canvas should NOT wrap to y+1 if the content is too long
     /// - canvas should handle wide characters
+    /// - content is split into grapheme clusters, each of which is put into a single cell
     /// return the printed width of the content
     fn print_with_attr(
         &mut self,
@@ -39,17 +41,33 @@ pub trait Canvas {
         content: &str,
         attr: Attr,
     ) -> Result<usize> {
-        let mut cell = Cell {
-            attr,
-            ..Cell::default()
-        };
-
-        let mut width = 0;
-        for ch in content.chars() {
-            cell.ch = ch;
-            width += self.put_cell(row, col + width, cell)?;
-        }
-        Ok(width)
+        print_cells(self, row, col, content, attr, None)
+    }
+
+    /// print `content` starting with position `(row, col)` with `attr` as a hyperlink to `link`,
+    /// terminals that support OSC 8 will make the content clickable.
+    /// return the printed width of the content
+    ///
+    /// ```
+    /// use tuikit::attr::{Attr, Effect};
+    /// use tuikit::canvas::Canvas;
+    /// use tuikit::cell::Hyperlink;
+    /// use tuikit::screen::Screen;
+    ///
+    /// let mut screen = Screen::new(20, 1);
+    /// let link = Hyperlink::new("https://example.com").unwrap();
+    /// let attr = Attr::from(Effect::UNDERLINE);
+    /// assert_eq!(7, screen.print_link(0, 0, "example", link, attr).unwrap());
+    /// ```
+    fn print_link(
+        &mut self,
+        row: usize,
+        col: usize,
+        content: &str,
+        link: Hyperlink,
+        attr: Attr,
+    ) -> Result<usize> {
+        print_cells(self, row, col, content, attr, Some(link))
     }
 
     /// print `content` starting with position `(row, col)` with default attribute
@@ -62,6 +80,35 @@ pub trait Canvas {
 
     /// show/hide cursor, set `show` to `false` to hide the cursor
     fn show_cursor(&mut self, show: bool) -> Result<()>;
+
+    /// change the shape and color of the cursor, e.g. a bar cursor for the insert mode.
+    /// Canvases that don't track the cursor style ignore it.
+    fn set_cursor_style(&mut self, _style: CursorStyle) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// put the grapheme clusters of `content` into the cells starting from `(row, col)`
+fn print_cells<C: Canvas + ?Sized>(
+    canvas: &mut C,
+    row: usize,
+    col: usize,
+    content: &str,
+    attr: Attr,
+    link: Option<Hyperlink>,
+) -> Result<usize> {
+    let mut cell = Cell {
+        attr,
+        link,
+        ..Cell::default()
+    };
+
+    let mut width = 0;
+    for grapheme in content.graphemes(true) {
+        cell.ch = grapheme.into();
+        width += canvas.put_cell(row, col + width, cell)?;
+    }
+    Ok(width)
 }
 
 /// A sub-area of a canvas.
@@ -111,7 +158,7 @@ impl<'a> Canvas for BoundedCanvas<'a> {
     fn put_cell(&mut self, row: usize, col: usize, cell: Cell) -> Result<usize> {
         if row >= self.height || col >= self.width {
             // do nothing
-            Ok(cell.ch.width().unwrap_or(2))
+            Ok(cell.ch.width())
         } else {
             self.canvas.put_cell(row + self.top, col + self.left, cell)
         }
@@ -129,4 +176,8 @@ impl<'a> Canvas for BoundedCanvas<'a> {
     fn show_cursor(&mut self, show: bool) -> Result<()> {
         self.canvas.show_cursor(show)
     }
+
+    fn set_cursor_style(&mut self, style: CursorStyle) -> Result<()> {
+        self.canvas.set_cursor_style(style)
+    }
 }
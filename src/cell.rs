@@ -1,20 +1,29 @@
 ///! `Cell` is a cell of the terminal.
 ///! It has a display character and an attribute (fg and bg color, effects).
 use crate::attr::{Attr, Color, Effect};
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Mutex;
+use unicode_width::UnicodeWidthChar;
 
 const EMPTY_CHAR: char = '\0';
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Cell {
-    pub ch: char,
+    /// the grapheme cluster to display, normally it is a single char
+    pub ch: Grapheme,
     pub attr: Attr,
+    /// the hyperlink (OSC 8) the cell belongs to
+    pub link: Option<Hyperlink>,
 }
 
 impl Default for Cell {
     fn default() -> Self {
         Self {
-            ch: ' ',
+            ch: Grapheme::from(' '),
             attr: Attr::default(),
+            link: None,
         }
     }
 }
@@ -25,7 +34,13 @@ impl Cell {
     }
 
     pub fn ch(mut self, ch: char) -> Self {
-        self.ch = ch;
+        self.ch = Grapheme::from(ch);
+        self
+    }
+
+    /// set the content to a grapheme cluster, e.g. emoji sequences, chars with combining marks
+    pub fn grapheme(mut self, grapheme: &str) -> Self {
+        self.ch = Grapheme::from(grapheme);
         self
     }
 
@@ -49,17 +64,282 @@ impl Cell {
         self
     }
 
+    pub fn link(mut self, link: Hyperlink) -> Self {
+        self.link = Some(link);
+        self
+    }
+
     /// check if a cell is empty
     pub fn is_empty(self) -> bool {
-        self.ch == EMPTY_CHAR && self.attr == Attr::default()
+        self.ch == EMPTY_CHAR && self.attr == Attr::default() && self.link.is_none()
     }
 }
 
 impl From<char> for Cell {
     fn from(ch: char) -> Self {
         Cell {
-            ch,
+            ch: Grapheme::from(ch),
             attr: Attr::default(),
+            link: None,
+        }
+    }
+}
+
+const INLINE_CAPACITY: usize = 22;
+
+/// A grapheme cluster, i.e. what users perceive as a single character.
+///
+/// Short clusters (including all single chars) are stored inline, longer ones are interned,
+/// so that `Grapheme` and thus `Cell` is cheap to copy.
+///
+/// ```
+/// use tuikit::cell::Grapheme;
+///
+/// let ch = Grapheme::from('a');
+/// assert_eq!('a', ch);
+/// assert_eq!(1, ch.width());
+///
+/// let family = Grapheme::from("👨‍👩‍👧‍👦");
+/// assert_eq!("👨‍👩‍👧‍👦", family.as_str());
+/// assert_eq!(2, family.width());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Grapheme(Repr);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Repr {
+    Inline {
+        len: u8,
+        bytes: [u8; INLINE_CAPACITY],
+    },
+    Interned(&'static str),
+}
+
+impl Grapheme {
+    pub fn as_str(&self) -> &str {
+        match self.0 {
+            Repr::Inline { len, ref bytes } => {
+                std::str::from_utf8(&bytes[..len as usize]).expect("grapheme: invalid utf8")
+            }
+            Repr::Interned(s) => s,
+        }
+    }
+
+    /// return the char if the grapheme consists of a single char
+    pub fn as_char(&self) -> Option<char> {
+        let mut chars = self.as_str().chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Some(ch),
+            _ => None,
+        }
+    }
+
+    /// the display width of the grapheme
+    pub fn width(&self) -> usize {
+        if let Some(ch) = self.as_char() {
+            return ch.width().unwrap_or(2);
+        }
+
+        // the width of a cluster is decided by its base char, except for emoji sequences that
+        // are displayed in emoji presentation.
+        let mut chars = self.as_str().chars();
+        let base = match chars.next() {
+            Some(ch) => ch,
+            None => return 0,
+        };
+        let is_emoji = chars.any(|ch| ch == '\u{FE0F}' || ch == '\u{200D}')
+            || ('\u{1F1E6}'..='\u{1F1FF}').contains(&base); // regional indicator, i.e. flags
+        if is_emoji {
+            2
+        } else {
+            base.width().unwrap_or(0).max(1)
+        }
+    }
+}
+
+impl Default for Grapheme {
+    fn default() -> Self {
+        Grapheme::from(' ')
+    }
+}
+
+impl From<char> for Grapheme {
+    fn from(ch: char) -> Self {
+        let mut bytes = [0; INLINE_CAPACITY];
+        let len = ch.encode_utf8(&mut bytes).len();
+        Grapheme(Repr::Inline {
+            len: len as u8,
+            bytes,
+        })
+    }
+}
+
+impl From<&str> for Grapheme {
+    /// the content should be a single grapheme cluster, it is not checked.
+    fn from(grapheme: &str) -> Self {
+        if grapheme.len() <= INLINE_CAPACITY {
+            let mut bytes = [0; INLINE_CAPACITY];
+            bytes[..grapheme.len()].copy_from_slice(grapheme.as_bytes());
+            Grapheme(Repr::Inline {
+                len: grapheme.len() as u8,
+                bytes,
+            })
+        } else if let Some(interned) = intern(grapheme) {
+            Grapheme(Repr::Interned(interned))
+        } else {
+            // too many long clusters, degrade to the base char
+            Grapheme::from(grapheme.chars().next().unwrap_or(' '))
+        }
+    }
+}
+
+impl PartialEq<char> for Grapheme {
+    fn eq(&self, other: &char) -> bool {
+        self.as_char() == Some(*other)
+    }
+}
+
+impl PartialEq<Grapheme> for char {
+    fn eq(&self, other: &Grapheme) -> bool {
+        other == self
+    }
+}
+
+impl fmt::Debug for Grapheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Grapheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A hyperlink (OSC 8) that cells could be attached to.
+///
+/// The URI (and the optional id) are interned, so that `Hyperlink` and thus `Cell` is cheap to
+/// copy. Terminals use the id to decide whether separated cells belong to the same link, e.g. to
+/// underline them together on hover.
+///
+/// The interned strings are kept for the life of the process and their total size is capped,
+/// `new` and `id` return `None` once the cap is reached.
+///
+/// ```
+/// use tuikit::cell::Hyperlink;
+///
+/// let link = Hyperlink::new("https://example.com").unwrap();
+/// let link = link.id("example").unwrap();
+/// assert_eq!("https://example.com", link.uri());
+/// assert_eq!(Some("example"), link.get_id());
+/// assert_eq!(Some(link), Hyperlink::new("https://example.com").and_then(|l| l.id("example")));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hyperlink {
+    uri: &'static str,
+    id: Option<&'static str>,
+}
+
+impl Hyperlink {
+    /// create a link to `uri`, `None` if too many distinct links were created
+    pub fn new(uri: &str) -> Option<Self> {
+        Some(Self {
+            uri: intern(uri)?,
+            id: None,
+        })
+    }
+
+    /// set the id of the link, `None` if too many distinct links were created
+    pub fn id(mut self, id: &str) -> Option<Self> {
+        self.id = Some(intern(id)?);
+        Some(self)
+    }
+
+    /// the URI the link points to
+    pub fn uri(&self) -> &str {
+        self.uri
+    }
+
+    /// the id set by `id`, if any
+    pub fn get_id(&self) -> Option<&str> {
+        self.id
+    }
+}
+
+// at most 1 MiB is kept by `intern`
+const MAX_INTERNED_BYTES: usize = 1 << 20;
+
+/// long clusters, links, etc. are rare and tend to repeat, keep a single copy of each of them.
+///
+/// The copies are never freed so that they could be referenced by `Copy` types, thus the total
+/// size is capped, `None` is returned once it is reached.
+pub(crate) fn intern(content: &str) -> Option<&'static str> {
+    lazy_static! {
+        static ref INTERNED: Mutex<Interned> = Mutex::new(Interned::default());
+    }
+
+    INTERNED
+        .lock()
+        .unwrap()
+        .get_or_insert(content, MAX_INTERNED_BYTES)
+}
+
+#[derive(Default)]
+struct Interned {
+    contents: HashSet<&'static str>,
+    bytes: usize,
+}
+
+impl Interned {
+    fn get_or_insert(&mut self, content: &str, max_bytes: usize) -> Option<&'static str> {
+        if let Some(&s) = self.contents.get(content) {
+            return Some(s);
+        }
+        if self.bytes + content.len() > max_bytes {
+            return None;
         }
+        let s: &'static str = Box::leak(content.to_string().into_boxed_str());
+        self.contents.insert(s);
+        self.bytes += s.len();
+        Some(s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_grapheme_width() {
+        assert_eq!(1, Grapheme::from('a').width());
+        assert_eq!(2, Grapheme::from('中').width());
+        assert_eq!(1, Grapheme::from("e\u{301}").width()); // e + combining acute accent
+        assert_eq!(2, Grapheme::from("🇨🇳").width()); // flag
+        assert_eq!(2, Grapheme::from("❤\u{FE0F}").width()); // emoji presentation
+        assert_eq!(2, Grapheme::from("👩‍🔬").width()); // ZWJ sequence
+    }
+
+    #[test]
+    fn test_grapheme_storage() {
+        assert_eq!("a", Grapheme::from('a').as_str());
+        assert_eq!(Some('a'), Grapheme::from("a").as_char());
+        assert_eq!(None, Grapheme::from("e\u{301}").as_char());
+
+        let family = "👨‍👩‍👧‍👦";
+        assert!(family.len() > INLINE_CAPACITY);
+        assert_eq!(Grapheme::from(family), Grapheme::from(family));
+        assert_eq!(family, Grapheme::from(family).as_str());
+        assert_eq!(family, Grapheme::from(family).to_string());
+    }
+
+    #[test]
+    fn test_intern_limit() {
+        let mut interned = Interned::default();
+        let a = interned.get_or_insert("abc", 5).unwrap();
+        assert_eq!("abc", a);
+        assert!(std::ptr::eq(a, interned.get_or_insert("abc", 5).unwrap()));
+        assert_eq!(None, interned.get_or_insert("def", 5));
+        assert_eq!(Some("de"), interned.get_or_insert("de", 5));
     }
 }
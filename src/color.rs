@@ -5,7 +5,7 @@
 /// Color::RED; // predefined values
 /// Color::Rgb(255, 0, 0); // RED
 /// ```
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Color {
     Default,
     AnsiValue(u8),
@@ -39,3 +39,171 @@ impl Default for Color {
         Color::Default
     }
 }
+
+/// The number of colors a terminal could display.
+///
+/// Colors beyond the capability are mapped to the nearest color the terminal supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    /// colors are disabled, e.g. `NO_COLOR` is set
+    NoColor,
+    /// the 16 ANSI colors
+    Ansi16,
+    /// the 256 color palette
+    Ansi256,
+    /// 24 bit RGB colors
+    TrueColor,
+}
+
+/// the default palette of xterm for the 16 ANSI colors
+const ANSI16_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// the levels of each component in the 6x6x6 color cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Color {
+    /// Map the color to the nearest one that could be displayed with `depth`.
+    ///
+    /// ```
+    /// use tuikit::attr::{Color, ColorDepth};
+    ///
+    /// assert_eq!(Color::Rgb(1, 2, 3), Color::Rgb(1, 2, 3).downgrade(ColorDepth::TrueColor));
+    /// assert_eq!(Color::AnsiValue(196), Color::Rgb(255, 0, 0).downgrade(ColorDepth::Ansi256));
+    /// assert_eq!(Color::AnsiValue(9), Color::Rgb(255, 0, 0).downgrade(ColorDepth::Ansi16));
+    /// assert_eq!(Color::Default, Color::RED.downgrade(ColorDepth::NoColor));
+    /// ```
+    pub fn downgrade(self, depth: ColorDepth) -> Color {
+        match (self, depth) {
+            (Color::Default, _) => Color::Default,
+            (_, ColorDepth::NoColor) => Color::Default,
+            (Color::Rgb(r, g, b), ColorDepth::Ansi256) => Color::AnsiValue(rgb_to_ansi256(r, g, b)),
+            (Color::Rgb(r, g, b), ColorDepth::Ansi16) => Color::AnsiValue(rgb_to_ansi16(r, g, b)),
+            (Color::AnsiValue(x), ColorDepth::Ansi16) if x >= 16 => {
+                let (r, g, b) = ansi256_to_rgb(x);
+                Color::AnsiValue(rgb_to_ansi16(r, g, b))
+            }
+            (color, _) => color,
+        }
+    }
+
+    /// The RGB value of the color, ANSI colors are looked up in the default palette of xterm.
+    /// return `None` for `Color::Default`.
+    ///
+    /// ```
+    /// use tuikit::attr::Color;
+    ///
+    /// assert_eq!(Some((255, 0, 0)), Color::AnsiValue(196).to_rgb());
+    /// assert_eq!(None, Color::Default.to_rgb());
+    /// ```
+    pub fn to_rgb(self) -> Option<(u8, u8, u8)> {
+        match self {
+            Color::AnsiValue(x) => Some(ansi256_to_rgb(x)),
+            Color::Rgb(r, g, b) => Some((r, g, b)),
+            _ => None,
+        }
+    }
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let diff = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2) as u32;
+    diff(r1, r2) + diff(g1, g2) + diff(b1, b2)
+}
+
+fn ansi256_to_rgb(x: u8) -> (u8, u8, u8) {
+    match x {
+        0..=15 => ANSI16_PALETTE[x as usize],
+        16..=231 => {
+            let x = x - 16;
+            (
+                CUBE_LEVELS[(x / 36) as usize],
+                CUBE_LEVELS[(x / 6 % 6) as usize],
+                CUBE_LEVELS[(x % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (x - 232);
+            (level, level, level)
+        }
+    }
+}
+
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    // the nearest color in the color cube
+    let cube_index = |v: u8| match v {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (v - 35) / 40,
+    };
+    let (ri, gi, bi) = (cube_index(r), cube_index(g), cube_index(b));
+    let cube = 16 + 36 * ri + 6 * gi + bi;
+
+    // the nearest color in the grayscale ramp
+    let average = ((u16::from(r) + u16::from(g) + u16::from(b)) / 3) as u8;
+    let gray = 232 + average.saturating_sub(3).min(233) / 10;
+
+    let rgb = (r, g, b);
+    if distance(ansi256_to_rgb(gray), rgb) < distance(ansi256_to_rgb(cube), rgb) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn rgb_to_ansi16(r: u8, g: u8, b: u8) -> u8 {
+    (0..16)
+        .min_by_key(|&x| distance(ANSI16_PALETTE[x as usize], (r, g, b)))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rgb_to_ansi256() {
+        assert_eq!(16, rgb_to_ansi256(0, 0, 0));
+        assert_eq!(231, rgb_to_ansi256(255, 255, 255));
+        assert_eq!(208, rgb_to_ansi256(255, 135, 0));
+        assert_eq!(244, rgb_to_ansi256(128, 128, 128));
+        assert_eq!(232, rgb_to_ansi256(8, 8, 8));
+
+        // colors of the palette should map to themselves
+        for x in 16..=255 {
+            let (r, g, b) = ansi256_to_rgb(x);
+            assert_eq!(x, rgb_to_ansi256(r, g, b));
+        }
+    }
+
+    #[test]
+    fn test_downgrade_to_ansi16() {
+        assert_eq!(
+            Color::AnsiValue(4),
+            Color::Rgb(0, 0, 200).downgrade(ColorDepth::Ansi16)
+        );
+        assert_eq!(
+            Color::AnsiValue(15),
+            Color::AnsiValue(231).downgrade(ColorDepth::Ansi16)
+        );
+        assert_eq!(
+            Color::AnsiValue(3),
+            Color::AnsiValue(3).downgrade(ColorDepth::Ansi16)
+        );
+    }
+}
@@ -1,8 +1,9 @@
 //! events a `Term` could return
 
 pub use crate::key::Key;
+use crate::output::ClipboardSelection;
 
-#[derive(Eq, PartialEq, Hash, Debug, Copy, Clone)]
+#[derive(Eq, PartialEq, Hash, Debug, Clone)]
 pub enum Event<UserEvent: Send + 'static = ()> {
     Key(Key),
     Resize {
@@ -10,6 +11,16 @@ pub enum Event<UserEvent: Send + 'static = ()> {
         height: usize,
     },
     Restarted,
+    /// contents pasted by the user, only if bracketed paste is enabled
+    Paste(String),
+    /// the terminal window gained focus, only if focus events are enabled
+    FocusGained,
+    /// the terminal window lost focus, only if focus events are enabled
+    FocusLost,
+    /// contents of the clipboard, reported after `Term::request_clipboard`
+    Clipboard(ClipboardSelection, String),
+    /// a timer added by `Term::add_timer`/`Term::add_timeout` with the id fired
+    Tick(usize),
     /// user defined signal 1
     User(UserEvent),
 
@@ -17,9 +17,14 @@ use std::time::{Duration, Instant};
 
 use nix::fcntl::{fcntl, FcntlArg, OFlag};
 
+use crate::attr::Color;
+use crate::base64;
+use crate::cell::intern;
 use crate::error::TuikitError;
 use crate::key::Key::*;
-use crate::key::{Key, MouseButton};
+use crate::key::{Key, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crate::key::{MouseButton, MouseEvent, MouseEventKind};
+use crate::output::ClipboardSelection;
 use crate::raw::get_tty;
 use crate::spinlock::SpinLock;
 use crate::sys::file::wait_until_ready;
@@ -41,6 +46,8 @@ pub struct KeyBoard {
 
     raw_mouse: bool,
     next_key: Option<Result<Key>>,
+    // the contents of the last clipboard report
+    clipboard: Option<String>,
     last_click: Key,
     last_click_time: SpinLock<Instant>,
 }
@@ -71,6 +78,7 @@ impl KeyBoard {
             byte_buf: Vec::new(),
             raw_mouse: false,
             next_key: None,
+            clipboard: None,
             last_click: Key::Null,
             last_click_time: SpinLock::new(Instant::now()),
         }
@@ -96,14 +104,18 @@ impl KeyBoard {
     fn fetch_bytes(&mut self, timeout: Duration) -> Result<()> {
         let mut reader_buf = [0; 1];
 
-        // clear interrupt signal
-        while let Ok(_) = self.sig_rx.read(&mut reader_buf) {}
-
-        wait_until_ready(
+        let ready = wait_until_ready(
             self.file.as_raw_fd(),
             Some(self.sig_rx.as_raw_fd()),
             timeout,
-        )?; // wait timeout
+        );
+
+        // clear interrupt signal only when it is consumed, otherwise an interrupt sent while we
+        // were not waiting would be lost.
+        if let Err(TuikitError::Interrupted) = ready {
+            while self.sig_rx.read(&mut reader_buf).is_ok() {}
+        }
+        ready?; // wait timeout
 
         self.read_unread_bytes();
         Ok(())
@@ -116,6 +128,44 @@ impl KeyBoard {
         }
     }
 
+    /// Take the contents of the clipboard reported by the last `Key::ClipboardReport`.
+    pub fn take_clipboard(&mut self) -> Option<String> {
+        self.clipboard.take()
+    }
+
+    /// Read the pasted contents till the end of a bracketed paste(`ESC [ 201 ~`), it should be
+    /// called after `Key::BracketedPasteStart` is received.
+    ///
+    /// If the end never comes, the contents received before no more bytes arrive in `timeout`
+    /// are returned.
+    pub fn read_bracketed_paste(&mut self, timeout: Duration) -> Result<String> {
+        const PASTE_END: &[u8] = b"\x1b[201~";
+        let mut searched = 0;
+        loop {
+            self.read_unread_bytes();
+            let found = self.byte_buf[searched..]
+                .windows(PASTE_END.len())
+                .position(|window| window == PASTE_END);
+            if let Some(pos) = found {
+                let pos = searched + pos;
+                let remain = self.byte_buf.split_off(pos + PASTE_END.len());
+                let mut content = std::mem::replace(&mut self.byte_buf, remain);
+                content.truncate(pos);
+                return Ok(String::from_utf8_lossy(&content).into_owned());
+            }
+            searched = self.byte_buf.len().saturating_sub(PASTE_END.len() - 1);
+
+            match self.fetch_bytes(timeout) {
+                Err(TuikitError::Timeout(_)) => {
+                    let content = std::mem::take(&mut self.byte_buf);
+                    return Ok(String::from_utf8_lossy(&content).into_owned());
+                }
+                Err(err) => return Err(err),
+                Ok(_) => {}
+            }
+        }
+    }
+
     #[allow(dead_code)]
     fn next_byte(&mut self) -> Result<u8> {
         self.next_byte_timeout(Duration::new(0, 0))
@@ -292,6 +342,8 @@ impl KeyBoard {
         match seq1 {
             '[' => self.escape_csi(),
             'O' => self.escape_o(),
+            ']' => self.parse_osc().unwrap_or(Ok(Alt(']'))),
+            'P' => self.parse_dcs().unwrap_or(Ok(Alt('P'))),
             _ => self.parse_alt(seq1),
         }
     }
@@ -307,13 +359,11 @@ impl KeyBoard {
                     Err(_) => return Ok(ESC),
                 }
 
-                match self.escape_csi() {
-                    Ok(Up) => Ok(AltUp),
-                    Ok(Down) => Ok(AltDown),
-                    Ok(Left) => Ok(AltLeft),
-                    Ok(Right) => Ok(AltRight),
-                    Ok(PageUp) => Ok(AltPageUp),
-                    Ok(PageDown) => Ok(AltPageDown),
+                match self.escape_csi().map(Key::to_key_event) {
+                    Ok(Some(event)) => Ok(Key::from(KeyEvent {
+                        modifiers: event.modifiers | KeyModifiers::ALT,
+                        ..event
+                    })),
                     _ => Err(TuikitError::UnknownSequence(format!("ESC ESC [ ..."))),
                 }
             }
@@ -350,11 +400,19 @@ impl KeyBoard {
     }
 
     fn escape_csi(&mut self) -> Result<Key> {
+        if let Some(report) = self.parse_private_report() {
+            return report;
+        }
+
         let cursor_pos = self.parse_cursor_report();
         if cursor_pos.is_ok() {
             return cursor_pos;
         }
 
+        if let Some(key) = self.parse_modified_key() {
+            return key;
+        }
+
         let seq2 = self.next_byte_timeout(KEY_WAIT)?;
         match seq2 {
             b'0' | b'9' => Err(TuikitError::UnknownSequence(format!("ESC [ {:x?}", seq2))),
@@ -378,34 +436,17 @@ impl KeyBoard {
             b'H' => Ok(Home),  // khome
             b'F' => Ok(End),
             b'Z' => Ok(BackTab),
+            b'I' => Ok(FocusIn),
+            b'O' => Ok(FocusOut),
             b'M' => {
                 // X10 emulation mouse encoding: ESC [ M Bxy (6 characters only)
                 let cb = self.next_byte_timeout(KEY_WAIT)?;
                 // (1, 1) are the coords for upper left.
                 let cx = self.next_byte_timeout(KEY_WAIT)?.saturating_sub(32) as u16 - 1; // 0 based
                 let cy = self.next_byte_timeout(KEY_WAIT)?.saturating_sub(32) as u16 - 1; // 0 based
-                match cb & 0b11 {
-                    0 => {
-                        if cb & 0x40 != 0 {
-                            Ok(MousePress(MouseButton::WheelUp, cy, cx))
-                        } else {
-                            Ok(MousePress(MouseButton::Left, cy, cx))
-                        }
-                    }
-                    1 => {
-                        if cb & 0x40 != 0 {
-                            Ok(MousePress(MouseButton::WheelDown, cy, cx))
-                        } else {
-                            Ok(MousePress(MouseButton::Middle, cy, cx))
-                        }
-                    }
-                    2 => Ok(MousePress(MouseButton::Right, cy, cx)),
-                    3 => Ok(MouseRelease(cy, cx)),
-                    _ => Err(TuikitError::UnknownSequence(format!(
-                        "ESC M {:?}{:?}{:?}",
-                        cb, cx, cy
-                    ))),
-                }
+                decode_mouse(u16::from(cb.saturating_sub(32)), false, cy, cx).ok_or_else(|| {
+                    TuikitError::UnknownSequence(format!("ESC M {:?}{:?}{:?}", cb, cx, cy))
+                })
             }
             b'<' => {
                 // xterm mouse encoding:
@@ -429,37 +470,9 @@ impl KeyBoard {
                 let cx = nums.next().unwrap().parse::<u16>().unwrap() - 1; // 0 based
                 let cy = nums.next().unwrap().parse::<u16>().unwrap() - 1; // 0 based
 
-                match cb {
-                    0..=2 | 64..=65 => {
-                        let button = match cb {
-                            0 => MouseButton::Left,
-                            1 => MouseButton::Middle,
-                            2 => MouseButton::Right,
-                            64 => MouseButton::WheelUp,
-                            65 => MouseButton::WheelDown,
-                            _ => {
-                                return Err(TuikitError::UnknownSequence(format!(
-                                    "ESC [ < {} {}",
-                                    str_buf, c
-                                )));
-                            }
-                        };
-
-                        match c {
-                            'M' => Ok(MousePress(button, cy, cx)),
-                            'm' => Ok(MouseRelease(cy, cx)),
-                            _ => Err(TuikitError::UnknownSequence(format!(
-                                "ESC [ < {} {}",
-                                str_buf, c
-                            ))),
-                        }
-                    }
-                    32 => Ok(MouseHold(cy, cx)),
-                    _ => Err(TuikitError::UnknownSequence(format!(
-                        "ESC [ < {} {}",
-                        str_buf, c
-                    ))),
-                }
+                decode_mouse(cb, c == 'm', cy, cx).ok_or_else(|| {
+                    TuikitError::UnknownSequence(format!("ESC [ < {} {}", str_buf, c))
+                })
             }
             _ => Err(TuikitError::UnknownSequence(format!("ESC [ {:?}", seq2))),
         }
@@ -491,6 +504,166 @@ impl KeyBoard {
         }
     }
 
+    /// reports with private parameters:
+    /// - DEC private mode (DECRPM): ESC [ ? mode ; setting $ y
+    /// - primary device attributes (DA1): ESC [ ? class ; attribute ; ... c
+    /// - flags of the kitty keyboard protocol: ESC [ ? flags u
+    fn parse_private_report(&mut self) -> Option<Result<Key>> {
+        self.read_unread_bytes();
+        if self.byte_buf.first() != Some(&b'?') {
+            return None;
+        }
+        let pos_final = self
+            .byte_buf
+            .iter()
+            .position(|&b| !(b'0'..=b'?').contains(&b))?;
+        let final_len = match (self.byte_buf[pos_final], self.byte_buf.get(pos_final + 1)) {
+            (b'$', Some(b'y')) => 2,
+            (b'c', _) | (b'u', _) => 1,
+            _ => return None,
+        };
+
+        let remain = self.byte_buf.split_off(pos_final + final_len);
+        let sequence = std::mem::replace(&mut self.byte_buf, remain);
+        let params = String::from_utf8_lossy(&sequence[1..pos_final]);
+        let numbers: Vec<Option<u16>> = params.split(';').map(|field| field.parse().ok()).collect();
+        let key = match (sequence[pos_final], numbers.as_slice()) {
+            (b'$', [Some(mode), Some(setting)]) => Some(ModeReport(*mode, *setting as u8)),
+            (b'c', [Some(class), attributes @ ..]) => {
+                let attributes = attributes
+                    .iter()
+                    .flatten()
+                    .filter(|&&attribute| attribute < 64)
+                    .fold(0, |bits, attribute| bits | 1 << attribute);
+                Some(DeviceAttributes(*class, attributes))
+            }
+            (b'u', [Some(flags)]) => Some(KittyKeyboardReport(*flags as u8)),
+            _ => None,
+        };
+        Some(key.ok_or_else(|| {
+            TuikitError::UnknownSequence(format!("ESC [ {}", String::from_utf8_lossy(&sequence)))
+        }))
+    }
+
+    /// device control strings: ESC P params ESC \\
+    /// - name and version of the terminal (XTVERSION): ESC P > | text ESC \\
+    ///
+    /// return `None` if the sequence is not terminated, i.e. it is an `Alt-P` key stroke.
+    fn parse_dcs(&mut self) -> Option<Result<Key>> {
+        self.read_unread_bytes();
+        let pos_end = self.byte_buf.windows(2).position(|w| w == b"\x1b\\")?;
+
+        let remain = self.byte_buf.split_off(pos_end + 2);
+        let mut sequence = std::mem::replace(&mut self.byte_buf, remain);
+        sequence.truncate(pos_end);
+        let params = String::from_utf8_lossy(&sequence);
+        match params.strip_prefix(">|") {
+            Some(version) => Some(Ok(TerminalVersion(intern(version).unwrap_or_default()))),
+            None => Some(Err(TuikitError::UnknownSequence(format!(
+                "ESC P {}",
+                params
+            )))),
+        }
+    }
+
+    /// the position and length of the terminator(BEL or ESC \\) of the buffered OSC
+    fn osc_end(&self) -> Option<(usize, usize)> {
+        self.byte_buf
+            .iter()
+            .enumerate()
+            .find_map(|(pos, &b)| match b {
+                b'\x07' => Some((pos, 1)),
+                b'\x1b' if self.byte_buf.get(pos + 1) == Some(&b'\\') => Some((pos, 2)),
+                _ => None,
+            })
+    }
+
+    /// replies of operating system commands: ESC ] params (BEL or ESC \\)
+    /// - colors(OSC 4/10/11): ESC ] 4 ; index ; rgb:r/g/b, ESC ] 10 ; rgb:r/g/b
+    /// - clipboard(OSC 52): ESC ] 52 ; selection ; base64, the contents are kept for
+    ///   `take_clipboard`
+    ///
+    /// return `None` if the sequence is not terminated, i.e. it is an `Alt-]` key stroke.
+    fn parse_osc(&mut self) -> Option<Result<Key>> {
+        self.read_unread_bytes();
+        let mut end = self.osc_end();
+
+        // long replies, e.g. clipboard contents, may arrive in pieces
+        while end.is_none() && matches!(self.byte_buf.first(), Some(b) if b.is_ascii_digit()) {
+            match self.fetch_bytes(KEY_WAIT) {
+                Err(TuikitError::Interrupted) => return Some(Err(TuikitError::Interrupted)),
+                Err(_) => break,
+                Ok(_) => end = self.osc_end(),
+            }
+        }
+        let (pos_end, terminator_len) = end?;
+
+        let remain = self.byte_buf.split_off(pos_end + terminator_len);
+        let mut sequence = std::mem::replace(&mut self.byte_buf, remain);
+        sequence.truncate(pos_end);
+        let params = String::from_utf8_lossy(&sequence);
+
+        let mut fields = params.split(';');
+        let key = match fields.next() {
+            Some("4") => fields
+                .next()
+                .and_then(|index| index.parse().ok())
+                .and_then(|index| Some(ColorReport(4, index, parse_rgb(fields.next()?)?))),
+            Some(code @ "10") | Some(code @ "11") => fields
+                .next()
+                .and_then(parse_rgb)
+                .map(|color| ColorReport(code.parse().unwrap(), 0, color)),
+            Some("52") => {
+                let selection = ClipboardSelection::from_param(fields.next().unwrap_or(""));
+                let content = fields.next().and_then(base64::decode);
+                content.map(|content| {
+                    self.clipboard = Some(String::from_utf8_lossy(&content).into_owned());
+                    ClipboardReport(selection)
+                })
+            }
+            _ => None,
+        };
+        Some(key.ok_or_else(|| TuikitError::UnknownSequence(format!("ESC ] {}", params))))
+    }
+
+    /// keys with modifiers:
+    /// - xterm: ESC [ number ; modifiers (~ or letter), ESC [ 27 ; modifiers ; code ~
+    /// - kitty keyboard protocol: ESC [ code[:shifted[:base]] ; modifiers[:event] [; text] u
+    ///   or the legacy forms with event types: ESC [ number ; modifiers:event (~ or letter)
+    fn parse_modified_key(&mut self) -> Option<Result<Key>> {
+        self.read_unread_bytes();
+        let pos_final = self
+            .byte_buf
+            .iter()
+            .position(|&b| !(b'0'..=b'?').contains(&b))?;
+        let params = &self.byte_buf[..pos_final];
+        let final_byte = self.byte_buf[pos_final];
+
+        let is_modified = match final_byte {
+            b'u' => true,
+            b'~' | b'A'..=b'F' | b'H' | b'P' | b'Q' | b'S' => {
+                params.contains(&b';') || params.contains(&b':')
+            }
+            _ => false,
+        };
+        if !is_modified {
+            return None;
+        }
+
+        let remain = self.byte_buf.split_off(pos_final + 1);
+        let mut sequence = std::mem::replace(&mut self.byte_buf, remain);
+        sequence.pop(); // remove the final byte
+        let params = String::from_utf8_lossy(&sequence);
+
+        Some(
+            parse_key_sequence(&params, final_byte)
+                .map(Key::from)
+                .ok_or_else(|| {
+                    TuikitError::UnknownSequence(format!("ESC [ {} {}", params, final_byte as char))
+                }),
+        )
+    }
+
     fn extended_escape(&mut self, seq2: u8) -> Result<Key> {
         let seq3 = self.next_byte_timeout(KEY_WAIT)?;
         if seq3 == b'~' {
@@ -524,16 +697,12 @@ impl KeyBoard {
                     let cx = nums.next().unwrap().parse::<u16>().unwrap() - 1; // 0 based
                     let cy = nums.next().unwrap().parse::<u16>().unwrap() - 1; // 0 based
 
-                    match cb {
-                        32 => Ok(MousePress(MouseButton::Left, cy, cx)),
-                        33 => Ok(MousePress(MouseButton::Middle, cy, cx)),
-                        34 => Ok(MousePress(MouseButton::Right, cy, cx)),
-                        35 => Ok(MouseRelease(cy, cx)),
-                        64 => Ok(MouseHold(cy, cx)),
-                        96 | 97 => Ok(MousePress(MouseButton::WheelUp, cy, cx)),
-                        _ => Err(TuikitError::UnknownSequence(format!("ESC [ {} M", str_buf))),
-                    }
+                    decode_mouse(cb.saturating_sub(32), false, cy, cx)
+                        .ok_or_else(|| TuikitError::UnknownSequence(format!("ESC [ {} M", str_buf)))
                 }
+                b'~' if str_buf.contains(';') => parse_key_sequence(&str_buf, seq_last)
+                    .map(Key::from)
+                    .ok_or_else(|| TuikitError::UnknownSequence(format!("ESC [ {} ~", str_buf))),
                 b'~' => {
                     let num: u8 = str_buf.parse().unwrap();
                     match num {
@@ -548,42 +717,22 @@ impl KeyBoard {
                 _ => unreachable!(),
             }
         } else if seq3 == b';' {
-            let seq4 = self.next_byte_timeout(KEY_WAIT)?;
-            if seq4 >= b'0' && seq4 <= b'9' {
-                let seq5 = self.next_byte_timeout(KEY_WAIT)?;
-                if seq2 == b'1' {
-                    match (seq4, seq5) {
-                        (b'5', b'A') => Ok(CtrlUp),
-                        (b'5', b'B') => Ok(CtrlDown),
-                        (b'5', b'C') => Ok(CtrlRight),
-                        (b'5', b'D') => Ok(CtrlLeft),
-                        (b'4', b'A') => Ok(AltShiftUp),
-                        (b'4', b'B') => Ok(AltShiftDown),
-                        (b'4', b'C') => Ok(AltShiftRight),
-                        (b'4', b'D') => Ok(AltShiftLeft),
-                        (b'3', b'H') => Ok(AltHome),
-                        (b'3', b'F') => Ok(AltEnd),
-                        (b'2', b'A') => Ok(ShiftUp),
-                        (b'2', b'B') => Ok(ShiftDown),
-                        (b'2', b'C') => Ok(ShiftRight),
-                        (b'2', b'D') => Ok(ShiftLeft),
-                        _ => Err(TuikitError::UnknownSequence(format!(
-                            "ESC [ 1 ; {:x?} {:x?}",
-                            seq4, seq5
-                        ))),
-                    }
-                } else {
-                    Err(TuikitError::UnknownSequence(format!(
-                        "ESC [ {:x?} ; {:x?} {:x?}",
-                        seq2, seq4, seq5
-                    )))
-                }
-            } else {
-                Err(TuikitError::UnknownSequence(format!(
-                    "ESC [ {:x?} ; {:x?}",
-                    seq2, seq4
-                )))
+            // ESC [ number ; modifiers (~ or letter)
+            let mut str_buf = String::new();
+            str_buf.push(seq2 as char);
+            str_buf.push(seq3 as char);
+
+            let mut seq_last = self.next_byte_timeout(KEY_WAIT)?;
+            while (b'0'..=b'?').contains(&seq_last) {
+                str_buf.push(seq_last as char);
+                seq_last = self.next_byte_timeout(KEY_WAIT)?;
             }
+
+            parse_key_sequence(&str_buf, seq_last)
+                .map(Key::from)
+                .ok_or_else(|| {
+                    TuikitError::UnknownSequence(format!("ESC [ {} {}", str_buf, seq_last as char))
+                })
         } else {
             match (seq2, seq3) {
                 (b'5', b'A') => Ok(CtrlUp),
@@ -621,6 +770,182 @@ impl KeyBoard {
     }
 }
 
+/// Parse the parameters and the final byte of a CSI sequence that represents a key with
+/// modifiers, in either xterm's or kitty keyboard protocol's format.
+fn parse_key_sequence(params: &str, final_byte: u8) -> Option<KeyEvent> {
+    let mut fields = params.split(';');
+
+    // code[:shifted[:base]]
+    let mut key_field = fields.next().unwrap_or("").split(':');
+    let number = match key_field.next() {
+        Some("") | None => 1,
+        Some(num) => num.parse::<u32>().ok()?,
+    };
+    let shifted = key_field.next().and_then(|num| num.parse::<u32>().ok());
+
+    // modifiers[:event]
+    let mut modifier_field = fields.next().unwrap_or("").split(':');
+    let modifiers = match modifier_field.next() {
+        Some("") | None => 1,
+        Some(num) => num.parse::<u16>().ok()?,
+    };
+    let modifiers = KeyModifiers::from_bits_truncate(modifiers.saturating_sub(1) as u8);
+    let kind = match modifier_field.next() {
+        Some("2") => KeyEventKind::Repeat,
+        Some("3") => KeyEventKind::Release,
+        _ => KeyEventKind::Press,
+    };
+
+    let mut code = match final_byte {
+        b'u' => kitty_key_code(number)?,
+        b'~' => match number {
+            2 => KeyCode::Insert,
+            3 => KeyCode::Delete,
+            5 => KeyCode::PageUp,
+            6 => KeyCode::PageDown,
+            7 => KeyCode::Home,
+            8 => KeyCode::End,
+            v @ 11..=15 => KeyCode::F((v - 10) as u8),
+            v @ 17..=21 => KeyCode::F((v - 11) as u8),
+            v @ 23..=24 => KeyCode::F((v - 12) as u8),
+            29 => KeyCode::Menu,
+            57427 => KeyCode::KeypadBegin,
+            // xterm's modifyOtherKeys: ESC [ 27 ; modifiers ; code ~
+            27 => kitty_key_code(fields.next()?.parse::<u32>().ok()?)?,
+            _ => return None,
+        },
+        b'A' => KeyCode::Up,
+        b'B' => KeyCode::Down,
+        b'C' => KeyCode::Right,
+        b'D' => KeyCode::Left,
+        b'E' => KeyCode::KeypadBegin,
+        b'F' => KeyCode::End,
+        b'H' => KeyCode::Home,
+        b'P' => KeyCode::F(1),
+        b'Q' => KeyCode::F(2),
+        b'S' => KeyCode::F(4),
+        _ => return None,
+    };
+
+    // report the shifted key if any, so that `Shift-a` would be `A` as in legacy mode
+    if let KeyCode::Char(ch) = code {
+        if modifiers.contains(KeyModifiers::SHIFT) {
+            code = match shifted.and_then(std::char::from_u32) {
+                Some(shifted) => KeyCode::Char(shifted),
+                None => KeyCode::Char(ch.to_ascii_uppercase()),
+            };
+        }
+    }
+
+    Some(KeyEvent::new(code, modifiers).kind(kind))
+}
+
+/// Map the unicode key code of the kitty keyboard protocol to `KeyCode`
+/// Refer to: https://sw.kovidgoyal.net/kitty/keyboard-protocol/#functional-key-definitions
+fn kitty_key_code(number: u32) -> Option<KeyCode> {
+    let code = match number {
+        9 => KeyCode::Tab,
+        13 => KeyCode::Enter,
+        27 => KeyCode::Esc,
+        127 => KeyCode::Backspace,
+        57358 => KeyCode::CapsLock,
+        57359 => KeyCode::ScrollLock,
+        57360 => KeyCode::NumLock,
+        57361 => KeyCode::PrintScreen,
+        57362 => KeyCode::Pause,
+        57363 => KeyCode::Menu,
+        v @ 57376..=57398 => KeyCode::F((v - 57376 + 13) as u8),
+        // keypad keys are reported as their normal counterparts
+        v @ 57399..=57408 => KeyCode::Char(std::char::from_digit(v - 57399, 10)?),
+        57409 => KeyCode::Char('.'),
+        57410 => KeyCode::Char('/'),
+        57411 => KeyCode::Char('*'),
+        57412 => KeyCode::Char('-'),
+        57413 => KeyCode::Char('+'),
+        57414 => KeyCode::Enter,
+        57415 => KeyCode::Char('='),
+        57416 => KeyCode::Char(','),
+        57417 => KeyCode::Left,
+        57418 => KeyCode::Right,
+        57419 => KeyCode::Up,
+        57420 => KeyCode::Down,
+        57421 => KeyCode::PageUp,
+        57422 => KeyCode::PageDown,
+        57423 => KeyCode::Home,
+        57424 => KeyCode::End,
+        57425 => KeyCode::Insert,
+        57426 => KeyCode::Delete,
+        57427 => KeyCode::KeypadBegin,
+        57441 | 57447 => KeyCode::Modifier(KeyModifiers::SHIFT),
+        57442 | 57448 => KeyCode::Modifier(KeyModifiers::CTRL),
+        57443 | 57449 => KeyCode::Modifier(KeyModifiers::ALT),
+        57444 | 57450 => KeyCode::Modifier(KeyModifiers::SUPER),
+        57445 | 57451 => KeyCode::Modifier(KeyModifiers::HYPER),
+        57446 | 57452 => KeyCode::Modifier(KeyModifiers::META),
+        v @ 57344..=63743 => KeyCode::Functional(v),
+        v => KeyCode::Char(std::char::from_u32(v)?),
+    };
+    Some(code)
+}
+
+/// decode the button byte(Cb) of mouse reports, `release` is only for the SGR encoding(`m`).
+/// Refer to: https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h2-Mouse-Tracking
+fn decode_mouse(cb: u16, release: bool, row: u16, col: u16) -> Option<Key> {
+    let mut modifiers = KeyModifiers::empty();
+    if cb & 0b100 != 0 {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    if cb & 0b1000 != 0 {
+        modifiers |= KeyModifiers::ALT;
+    }
+    if cb & 0b10000 != 0 {
+        modifiers |= KeyModifiers::CTRL;
+    }
+
+    let is_motion = cb & 0b100000 != 0;
+    let button = match (cb & 0b11, cb & 0b1000000 != 0) {
+        (0, false) => Some(MouseButton::Left),
+        (1, false) => Some(MouseButton::Middle),
+        (2, false) => Some(MouseButton::Right),
+        (0, true) => Some(MouseButton::WheelUp),
+        (1, true) => Some(MouseButton::WheelDown),
+        (3, false) => None, // release or motion without button
+        _ => return None,   // horizontal wheels
+    };
+
+    let kind = match (button, is_motion) {
+        (_, false) if release => MouseEventKind::Release,
+        (Some(button), false) => MouseEventKind::Press(button),
+        (None, false) => MouseEventKind::Release,
+        (Some(button), true) => MouseEventKind::Drag(button),
+        (None, true) => MouseEventKind::Move,
+    };
+    Some(Key::from(
+        MouseEvent::new(kind, row, col).modifiers(modifiers),
+    ))
+}
+
+/// parse colors in the form of `rgb:r/g/b`, each component has 1 to 4 hex digits.
+fn parse_rgb(spec: &str) -> Option<Color> {
+    let mut components = spec.strip_prefix("rgb:")?.split('/').map(|component| {
+        let value = u32::from_str_radix(component, 16).ok()?;
+        let max = match component.len() {
+            1..=4 => (1 << (4 * component.len())) - 1,
+            _ => return None,
+        };
+        Some((value * 255 / max) as u8)
+    });
+    let color = Color::Rgb(
+        components.next()??,
+        components.next()??,
+        components.next()??,
+    );
+    match components.next() {
+        None => Some(color),
+        Some(_) => None,
+    }
+}
+
 pub struct KeyboardHandler {
     handler: Arc<SpinLock<File>>,
 }
@@ -632,3 +957,327 @@ impl KeyboardHandler {
         let _ = handler.flush();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::key::from_keyname;
+
+    fn key_event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_parse_key_sequence() {
+        let none = KeyModifiers::empty();
+        assert_eq!(
+            Some(key_event(KeyCode::Char('a'), none)),
+            parse_key_sequence("97", b'u')
+        );
+        assert_eq!(
+            Some(key_event(KeyCode::Char('i'), KeyModifiers::CTRL)),
+            parse_key_sequence("105;5", b'u')
+        );
+        assert_eq!(
+            Some(key_event(KeyCode::Enter, KeyModifiers::SHIFT)),
+            parse_key_sequence("13;2", b'u')
+        );
+        assert_eq!(
+            Some(key_event(KeyCode::Char('A'), KeyModifiers::SHIFT)),
+            parse_key_sequence("97:65;2", b'u')
+        );
+        assert_eq!(
+            Some(key_event(KeyCode::Char('a'), none).kind(KeyEventKind::Release)),
+            parse_key_sequence("97;1:3", b'u')
+        );
+        assert_eq!(
+            Some(key_event(KeyCode::Up, KeyModifiers::CTRL).kind(KeyEventKind::Repeat)),
+            parse_key_sequence("1;5:2", b'A')
+        );
+        assert_eq!(
+            Some(key_event(KeyCode::F(5), KeyModifiers::SUPER)),
+            parse_key_sequence("15;9", b'~')
+        );
+        assert_eq!(
+            Some(key_event(
+                KeyCode::Modifier(KeyModifiers::SHIFT),
+                KeyModifiers::SHIFT
+            )),
+            parse_key_sequence("57441;2", b'u')
+        );
+        assert_eq!(None, parse_key_sequence("1;x", b'u'));
+
+        // xterm
+        assert_eq!(
+            Some(key_event(
+                KeyCode::Left,
+                KeyModifiers::CTRL | KeyModifiers::SHIFT
+            )),
+            parse_key_sequence("1;6", b'D')
+        );
+        assert_eq!(
+            Some(key_event(KeyCode::Delete, KeyModifiers::CTRL)),
+            parse_key_sequence("3;5", b'~')
+        );
+        assert_eq!(
+            Some(key_event(KeyCode::Enter, KeyModifiers::CTRL)),
+            parse_key_sequence("27;5;13", b'~')
+        );
+    }
+
+    #[test]
+    fn test_kitty_keys() {
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let mut input = unsafe { File::from_raw_fd(tx) };
+        let mut keyboard = KeyBoard::new(Box::new(unsafe { File::from_raw_fd(rx) }));
+
+        input.write_all(b"\x1b[105;5u").unwrap();
+        assert_eq!(Ctrl('i'), keyboard.next_key().unwrap());
+
+        input.write_all(b"\x1b[9u").unwrap();
+        assert_eq!(Tab, keyboard.next_key().unwrap());
+
+        input.write_all(b"\x1b[13;2u").unwrap();
+        assert_eq!(
+            Key::Extended(KeyEvent::new(KeyCode::Enter, KeyModifiers::SHIFT)),
+            keyboard.next_key().unwrap()
+        );
+
+        input.write_all(b"\x1b[1;1:3D").unwrap();
+        assert_eq!(
+            Key::Extended(
+                KeyEvent::new(KeyCode::Left, KeyModifiers::empty()).kind(KeyEventKind::Release)
+            ),
+            keyboard.next_key().unwrap()
+        );
+
+        // legacy sequences still work
+        input.write_all(b"\x1b[A").unwrap();
+        assert_eq!(Up, keyboard.next_key().unwrap());
+    }
+
+    #[test]
+    fn test_modified_keys() {
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let mut input = unsafe { File::from_raw_fd(tx) };
+        let mut keyboard = KeyBoard::new(Box::new(unsafe { File::from_raw_fd(rx) }));
+
+        input.write_all(b"\x1b[1;5A").unwrap();
+        assert_eq!(CtrlUp, keyboard.next_key().unwrap());
+
+        input.write_all(b"\x1b[1;3H").unwrap();
+        assert_eq!(AltHome, keyboard.next_key().unwrap());
+
+        input.write_all(b"\x1b[1;6D").unwrap();
+        assert_eq!(
+            Some(Key::Extended(KeyEvent::new(
+                KeyCode::Left,
+                KeyModifiers::CTRL | KeyModifiers::SHIFT
+            ))),
+            from_keyname("ctrl-shift-left")
+        );
+        assert_eq!(
+            from_keyname("ctrl-shift-left").unwrap(),
+            keyboard.next_key().unwrap()
+        );
+
+        input.write_all(b"\x1b[1;5H").unwrap();
+        assert_eq!(from_keyname("ctrl-home"), keyboard.next_key().ok());
+
+        input.write_all(b"\x1b[15;2~").unwrap();
+        assert_eq!(from_keyname("shift-f5"), keyboard.next_key().ok());
+
+        input.write_all(b"\x1b[3;5~").unwrap();
+        assert_eq!(from_keyname("ctrl-del"), keyboard.next_key().ok());
+
+        input.write_all(b"\x1b\x1b[5;5~").unwrap();
+        assert_eq!(from_keyname("ctrl-alt-pgup"), keyboard.next_key().ok());
+
+        input.write_all(b"\x1b\x1b[B").unwrap();
+        assert_eq!(AltDown, keyboard.next_key().unwrap());
+    }
+
+    #[test]
+    fn test_mode_report() {
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let mut input = unsafe { File::from_raw_fd(tx) };
+        let mut keyboard = KeyBoard::new(Box::new(unsafe { File::from_raw_fd(rx) }));
+
+        input.write_all(b"\x1b[?2026;2$y").unwrap();
+        assert_eq!(ModeReport(2026, 2), keyboard.next_key().unwrap());
+
+        input.write_all(b"\x1b[?2026;0$y\x1b[3;1R").unwrap();
+        assert_eq!(ModeReport(2026, 0), keyboard.next_key().unwrap());
+        assert_eq!(CursorPos(2, 0), keyboard.next_key().unwrap());
+    }
+
+    #[test]
+    fn test_color_report() {
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let mut input = unsafe { File::from_raw_fd(tx) };
+        let mut keyboard = KeyBoard::new(Box::new(unsafe { File::from_raw_fd(rx) }));
+
+        input
+            .write_all(b"\x1b]11;rgb:ffff/8080/0000\x1b\\")
+            .unwrap();
+        assert_eq!(
+            ColorReport(11, 0, Color::Rgb(255, 128, 0)),
+            keyboard.next_key().unwrap()
+        );
+
+        input.write_all(b"\x1b]10;rgb:f/80/000\x07").unwrap();
+        assert_eq!(
+            ColorReport(10, 0, Color::Rgb(255, 128, 0)),
+            keyboard.next_key().unwrap()
+        );
+
+        input
+            .write_all(b"\x1b]4;1;rgb:cdcd/0000/0000\x07\x1b[3;1R")
+            .unwrap();
+        assert_eq!(
+            ColorReport(4, 1, Color::Rgb(205, 0, 0)),
+            keyboard.next_key().unwrap()
+        );
+        assert_eq!(CursorPos(2, 0), keyboard.next_key().unwrap());
+
+        // not terminated, it is a key stroke
+        input.write_all(b"\x1b]").unwrap();
+        assert_eq!(Alt(']'), keyboard.next_key().unwrap());
+    }
+
+    #[test]
+    fn test_capability_reports() {
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let mut input = unsafe { File::from_raw_fd(tx) };
+        let mut keyboard = KeyBoard::new(Box::new(unsafe { File::from_raw_fd(rx) }));
+
+        input.write_all(b"\x1bP>|kitty(0.26.5)\x1b\\").unwrap();
+        assert_eq!(
+            TerminalVersion("kitty(0.26.5)"),
+            keyboard.next_key().unwrap()
+        );
+
+        input.write_all(b"\x1b[?62;4;22c").unwrap();
+        assert_eq!(
+            DeviceAttributes(62, 1 << 4 | 1 << 22),
+            keyboard.next_key().unwrap()
+        );
+
+        input.write_all(b"\x1b[?1u\x1b[3;1R").unwrap();
+        assert_eq!(KittyKeyboardReport(1), keyboard.next_key().unwrap());
+        assert_eq!(CursorPos(2, 0), keyboard.next_key().unwrap());
+
+        input.write_all(b"\x1bP").unwrap();
+        assert_eq!(Alt('P'), keyboard.next_key().unwrap());
+    }
+
+    #[test]
+    fn test_bracketed_paste() {
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let mut input = unsafe { File::from_raw_fd(tx) };
+        let mut keyboard = KeyBoard::new(Box::new(unsafe { File::from_raw_fd(rx) }));
+
+        input
+            .write_all(b"\x1b[200~hello\r\n\x1b[Aworld\x1b[201~x")
+            .unwrap();
+        assert_eq!(BracketedPasteStart, keyboard.next_key().unwrap());
+        assert_eq!(
+            "hello\r\n\x1b[Aworld",
+            keyboard.read_bracketed_paste(KEY_WAIT).unwrap()
+        );
+        assert_eq!(Char('x'), keyboard.next_key().unwrap());
+
+        // the end marker is missing
+        input.write_all(b"\x1b[200~abc").unwrap();
+        assert_eq!(BracketedPasteStart, keyboard.next_key().unwrap());
+        assert_eq!("abc", keyboard.read_bracketed_paste(KEY_WAIT).unwrap());
+    }
+
+    #[test]
+    fn test_focus_report() {
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let mut input = unsafe { File::from_raw_fd(tx) };
+        let mut keyboard = KeyBoard::new(Box::new(unsafe { File::from_raw_fd(rx) }));
+
+        input.write_all(b"\x1b[O\x1b[I").unwrap();
+        assert_eq!(FocusOut, keyboard.next_key().unwrap());
+        assert_eq!(FocusIn, keyboard.next_key().unwrap());
+    }
+
+    #[test]
+    fn test_mouse_tracking() {
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let mut input = unsafe { File::from_raw_fd(tx) };
+        let mut keyboard =
+            KeyBoard::new(Box::new(unsafe { File::from_raw_fd(rx) })).raw_mouse(true);
+
+        // SGR encoding
+        input.write_all(b"\x1b[<32;3;2M").unwrap();
+        assert_eq!(
+            MouseDrag(MouseButton::Left, 1, 2),
+            keyboard.next_key().unwrap()
+        );
+        input.write_all(b"\x1b[<34;3;2M").unwrap();
+        assert_eq!(
+            MouseDrag(MouseButton::Right, 1, 2),
+            keyboard.next_key().unwrap()
+        );
+        input.write_all(b"\x1b[<35;3;2M").unwrap();
+        assert_eq!(MouseMove(1, 2), keyboard.next_key().unwrap());
+        input.write_all(b"\x1b[<0;3;2m").unwrap();
+        assert_eq!(MouseRelease(1, 2), keyboard.next_key().unwrap());
+
+        // modifiers
+        input.write_all(b"\x1b[<20;3;2M").unwrap();
+        let event = MouseEvent::new(MouseEventKind::Press(MouseButton::Left), 1, 2)
+            .modifiers(KeyModifiers::SHIFT | KeyModifiers::CTRL);
+        assert_eq!(Mouse(event), keyboard.next_key().unwrap());
+        input.write_all(b"\x1b[<72;3;2M").unwrap();
+        let event = MouseEvent::new(MouseEventKind::Press(MouseButton::WheelUp), 1, 2)
+            .modifiers(KeyModifiers::ALT);
+        assert_eq!(Mouse(event), keyboard.next_key().unwrap());
+
+        // X10 and rxvt encoding
+        input.write_all(b"\x1b[M@#\"").unwrap();
+        assert_eq!(
+            MouseDrag(MouseButton::Left, 1, 2),
+            keyboard.next_key().unwrap()
+        );
+        input.write_all(b"\x1b[67;3;2M").unwrap();
+        assert_eq!(MouseMove(1, 2), keyboard.next_key().unwrap());
+        input.write_all(b"\x1b[34;3;2M").unwrap();
+        assert_eq!(
+            MousePress(MouseButton::Right, 1, 2),
+            keyboard.next_key().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_clipboard_report() {
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let mut input = unsafe { File::from_raw_fd(tx) };
+        let mut keyboard = KeyBoard::new(Box::new(unsafe { File::from_raw_fd(rx) }));
+
+        input.write_all(b"\x1b]52;c;aGVsbG8=\x1b\\").unwrap();
+        assert_eq!(
+            ClipboardReport(ClipboardSelection::Clipboard),
+            keyboard.next_key().unwrap()
+        );
+        assert_eq!(Some("hello".to_string()), keyboard.take_clipboard());
+        assert_eq!(None, keyboard.take_clipboard());
+
+        // the reply arrives in pieces
+        input.write_all(b"\x1b]52;p;d29y").unwrap();
+        let mut writer = input.try_clone().unwrap();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(5));
+            writer.write_all(b"bGQ=\x07").unwrap();
+        });
+        assert_eq!(
+            ClipboardReport(ClipboardSelection::Primary),
+            keyboard.next_key().unwrap()
+        );
+        assert_eq!(Some("world".to_string()), keyboard.take_clipboard());
+        handle.join().unwrap();
+    }
+}
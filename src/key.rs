@@ -1,5 +1,9 @@
 //! Defines all the keys `tuikit` recognizes.
 
+use crate::attr::Color;
+use crate::output::ClipboardSelection;
+use bitflags::bitflags;
+
 // http://ascii-table.com/ansi-escape-sequences.php
 /// Single key
 #[rustfmt::skip]
@@ -29,11 +33,25 @@ pub enum Key {
     Alt(char),  // chars could be lower or upper case
     Char(char), // chars could be lower or upper case
     CursorPos(u16, u16), // row, col
+    ModeReport(u16, u8), // mode, setting: 0(not recognized), 1(set), 2(reset), 3(permanently set), 4(permanently reset)
+    ColorReport(u16, u8, Color), // OSC code: 4(palette), 10(foreground), 11(background), palette index(only for 4), color
+    DeviceAttributes(u16, u64), // primary device attributes(DA1): class, bit n is set if attribute n(< 64) is reported
+    KittyKeyboardReport(u8), // the current flags of the kitty keyboard protocol
+    TerminalVersion(&'static str), // name and version of the terminal(XTVERSION), e.g. "kitty(0.26.5)"
+    ClipboardReport(ClipboardSelection), // clipboard contents(OSC 52) reported, see `KeyBoard::take_clipboard`
 
     // raw mouse events, will only generated if raw mouse mode is enabled
     MousePress(MouseButton, u16, u16), // row, col
     MouseRelease(u16, u16), // row, col
-    MouseHold(u16, u16), // row, col
+    MouseHold(u16, u16), // row, col, superseded by `MouseDrag` which reports the button
+
+    // mouse motion, will only be generated if drag/any-event mouse tracking is enabled
+    MouseDrag(MouseButton, u16, u16), // button, row, col
+    MouseMove(u16, u16), // row, col, motion without any button pressed
+
+    // mouse events with modifiers(shift, alt, ctrl) held, always reported raw even if raw mouse
+    // mode is disabled, i.e. they are never parsed into `SingleClick`/`DoubleClick`/`WheelUp`/...
+    Mouse(MouseEvent),
 
     // parsed mouse events, will be generated if raw mouse mode is disabled
     SingleClick(MouseButton, u16, u16), // row, col
@@ -44,6 +62,14 @@ pub enum Key {
     BracketedPasteStart,
     BracketedPasteEnd,
 
+    // focus reports, will only be generated if focus events are enabled
+    FocusIn,
+    FocusOut,
+
+    // keys that could not be represented by the variants above, e.g. key release events and
+    // keys with modifiers reported by the kitty keyboard protocol
+    Extended(KeyEvent),
+
     #[doc(hidden)]
     __Nonexhaustive,
 
@@ -68,6 +94,361 @@ pub enum MouseButton {
     WheelDown,
 }
 
+/// What happened to the mouse.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MouseEventKind {
+    Press(MouseButton),
+    Release,
+    /// The mouse moved with the button pressed
+    Drag(MouseButton),
+    /// The mouse moved without any button pressed
+    Move,
+}
+
+/// A mouse event together with the modifiers.
+///
+/// ```
+/// use tuikit::key::{Key, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+///
+/// let event = MouseEvent::new(MouseEventKind::Drag(MouseButton::Left), 1, 2);
+/// assert_eq!(Key::MouseDrag(MouseButton::Left, 1, 2), Key::from(event));
+///
+/// let event = event.modifiers(KeyModifiers::CTRL);
+/// assert_eq!(Key::Mouse(event), Key::from(event));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MouseEvent {
+    pub kind: MouseEventKind,
+    pub row: u16,
+    pub col: u16,
+    pub modifiers: KeyModifiers,
+}
+
+impl MouseEvent {
+    pub fn new(kind: MouseEventKind, row: u16, col: u16) -> Self {
+        Self {
+            kind,
+            row,
+            col,
+            modifiers: KeyModifiers::empty(),
+        }
+    }
+
+    pub fn modifiers(mut self, modifiers: KeyModifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+}
+
+impl From<MouseEvent> for Key {
+    /// Convert to the legacy mouse keys if no modifiers are held, otherwise `Key::Mouse`
+    fn from(event: MouseEvent) -> Self {
+        if !event.modifiers.is_empty() {
+            return Key::Mouse(event);
+        }
+
+        let MouseEvent { row, col, .. } = event;
+        match event.kind {
+            MouseEventKind::Press(button) => Key::MousePress(button, row, col),
+            MouseEventKind::Release => Key::MouseRelease(row, col),
+            MouseEventKind::Drag(button) => Key::MouseDrag(button, row, col),
+            MouseEventKind::Move => Key::MouseMove(row, col),
+        }
+    }
+}
+
+bitflags! {
+    /// The modifiers that are held when a key is pressed.
+    ///
+    /// The bits are the same as the ones reported by the kitty keyboard protocol.
+    pub struct KeyModifiers: u8 {
+        const SHIFT = 0b00000001;
+        const ALT = 0b00000010;
+        const CTRL = 0b00000100;
+        const SUPER = 0b00001000;
+        const HYPER = 0b00010000;
+        const META = 0b00100000;
+        const CAPS_LOCK = 0b01000000;
+        const NUM_LOCK = 0b10000000;
+    }
+}
+
+/// The key that is pressed, regardless of the modifiers.
+#[derive(Eq, PartialEq, Hash, Debug, Copy, Clone)]
+pub enum KeyCode {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Esc,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    F(u8),
+    CapsLock,
+    ScrollLock,
+    NumLock,
+    PrintScreen,
+    Pause,
+    Menu,
+    KeypadBegin,
+    /// A modifier key(e.g. left shift) pressed on its own
+    Modifier(KeyModifiers),
+    /// Other functional keys(e.g. media keys) identified by their kitty keyboard protocol code
+    Functional(u32),
+}
+
+/// Whether a key is pressed, repeated (being held) or released.
+#[derive(Eq, PartialEq, Hash, Debug, Copy, Clone)]
+pub enum KeyEventKind {
+    Press,
+    Repeat,
+    Release,
+}
+
+/// A key together with its modifiers and event kind.
+///
+/// ```
+/// use tuikit::key::{Key, KeyCode, KeyEvent, KeyModifiers};
+///
+/// let event = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CTRL);
+/// assert_eq!(Key::Ctrl('a'), Key::from(event));
+///
+/// let event = KeyEvent::new(KeyCode::Enter, KeyModifiers::SHIFT);
+/// assert_eq!(Key::Extended(event), Key::from(event));
+/// ```
+#[derive(Eq, PartialEq, Hash, Debug, Copy, Clone)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+    pub kind: KeyEventKind,
+}
+
+impl KeyEvent {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+        }
+    }
+
+    pub fn kind(mut self, kind: KeyEventKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+
+impl Key {
+    /// Convert to `KeyEvent`, return `None` if the key is not a key stroke(e.g. mouse events)
+    ///
+    /// ```
+    /// use tuikit::key::{Key, KeyCode, KeyEvent, KeyModifiers};
+    ///
+    /// assert_eq!(
+    ///     Some(KeyEvent::new(KeyCode::Up, KeyModifiers::ALT | KeyModifiers::SHIFT)),
+    ///     Key::AltShiftUp.to_key_event()
+    /// );
+    /// assert_eq!(None, Key::MouseRelease(0, 0).to_key_event());
+    /// ```
+    #[rustfmt::skip]
+    pub fn to_key_event(self) -> Option<KeyEvent> {
+        use self::Key::*;
+        let shift = KeyModifiers::SHIFT;
+        let alt = KeyModifiers::ALT;
+        let ctrl = KeyModifiers::CTRL;
+        let none = KeyModifiers::empty();
+
+        let (code, modifiers) = match self {
+            ESC          => (KeyCode::Esc, none),
+            Ctrl(ch)     => (KeyCode::Char(ch), ctrl),
+            Tab          => (KeyCode::Tab, none),
+            Enter        => (KeyCode::Enter, none),
+            BackTab      => (KeyCode::Tab, shift),
+            Backspace    => (KeyCode::Backspace, none),
+            AltBackTab   => (KeyCode::Tab, alt | shift),
+
+            Up           => (KeyCode::Up, none),
+            Down         => (KeyCode::Down, none),
+            Left         => (KeyCode::Left, none),
+            Right        => (KeyCode::Right, none),
+            Home         => (KeyCode::Home, none),
+            End          => (KeyCode::End, none),
+            Insert       => (KeyCode::Insert, none),
+            Delete       => (KeyCode::Delete, none),
+            PageUp       => (KeyCode::PageUp, none),
+            PageDown     => (KeyCode::PageDown, none),
+            CtrlUp       => (KeyCode::Up, ctrl),
+            CtrlDown     => (KeyCode::Down, ctrl),
+            CtrlLeft     => (KeyCode::Left, ctrl),
+            CtrlRight    => (KeyCode::Right, ctrl),
+            ShiftUp      => (KeyCode::Up, shift),
+            ShiftDown    => (KeyCode::Down, shift),
+            ShiftLeft    => (KeyCode::Left, shift),
+            ShiftRight   => (KeyCode::Right, shift),
+            AltUp        => (KeyCode::Up, alt),
+            AltDown      => (KeyCode::Down, alt),
+            AltLeft      => (KeyCode::Left, alt),
+            AltRight     => (KeyCode::Right, alt),
+            AltHome      => (KeyCode::Home, alt),
+            AltEnd       => (KeyCode::End, alt),
+            AltPageUp    => (KeyCode::PageUp, alt),
+            AltPageDown  => (KeyCode::PageDown, alt),
+            AltShiftUp   => (KeyCode::Up, alt | shift),
+            AltShiftDown => (KeyCode::Down, alt | shift),
+            AltShiftLeft => (KeyCode::Left, alt | shift),
+            AltShiftRight => (KeyCode::Right, alt | shift),
+
+            F(n)         => (KeyCode::F(n), none),
+            CtrlAlt(ch)  => (KeyCode::Char(ch), ctrl | alt),
+            AltEnter     => (KeyCode::Enter, alt),
+            AltBackspace => (KeyCode::Backspace, alt),
+            AltTab       => (KeyCode::Tab, alt),
+            Alt(ch)      => (KeyCode::Char(ch), alt),
+            Char(ch)     => (KeyCode::Char(ch), none),
+            Extended(event) => return Some(event),
+            _ => return None,
+        };
+
+        Some(KeyEvent::new(code, modifiers))
+    }
+}
+
+impl Key {
+    /// Convert to `MouseEvent`, return `None` if the key is not a mouse event.
+    ///
+    /// Clicks are converted to presses, and merged wheel events to a single press.
+    ///
+    /// ```
+    /// use tuikit::key::{Key, MouseButton, MouseEvent, MouseEventKind};
+    ///
+    /// assert_eq!(
+    ///     Some(MouseEvent::new(MouseEventKind::Press(MouseButton::Left), 1, 2)),
+    ///     Key::SingleClick(MouseButton::Left, 1, 2).to_mouse_event()
+    /// );
+    /// assert_eq!(None, Key::Up.to_mouse_event());
+    /// ```
+    pub fn to_mouse_event(self) -> Option<MouseEvent> {
+        use self::Key::*;
+        let (kind, row, col) = match self {
+            MousePress(button, row, col)
+            | SingleClick(button, row, col)
+            | DoubleClick(button, row, col) => (MouseEventKind::Press(button), row, col),
+            MouseRelease(row, col) => (MouseEventKind::Release, row, col),
+            MouseHold(row, col) => (MouseEventKind::Drag(MouseButton::Left), row, col),
+            MouseDrag(button, row, col) => (MouseEventKind::Drag(button), row, col),
+            MouseMove(row, col) => (MouseEventKind::Move, row, col),
+            WheelUp(row, col, _) => (MouseEventKind::Press(MouseButton::WheelUp), row, col),
+            WheelDown(row, col, _) => (MouseEventKind::Press(MouseButton::WheelDown), row, col),
+            Mouse(event) => return Some(event),
+            _ => return None,
+        };
+        Some(MouseEvent::new(kind, row, col))
+    }
+
+    /// Move the position of a mouse key to `f(row, col)`, the key is dropped if `f` returns
+    /// `None`, e.g. when the position is out of some area. Other keys are returned unchanged.
+    pub(crate) fn map_mouse_position<F>(self, f: F) -> Option<Key>
+    where
+        F: FnOnce(u16, u16) -> Option<(u16, u16)>,
+    {
+        use self::Key::*;
+        let key = match self {
+            MousePress(button, row, col) => f(row, col).map(|(r, c)| MousePress(button, r, c))?,
+            MouseRelease(row, col) => f(row, col).map(|(r, c)| MouseRelease(r, c))?,
+            MouseHold(row, col) => f(row, col).map(|(r, c)| MouseHold(r, c))?,
+            MouseDrag(button, row, col) => f(row, col).map(|(r, c)| MouseDrag(button, r, c))?,
+            MouseMove(row, col) => f(row, col).map(|(r, c)| MouseMove(r, c))?,
+            SingleClick(button, row, col) => f(row, col).map(|(r, c)| SingleClick(button, r, c))?,
+            DoubleClick(button, row, col) => f(row, col).map(|(r, c)| DoubleClick(button, r, c))?,
+            WheelUp(row, col, count) => f(row, col).map(|(r, c)| WheelUp(r, c, count))?,
+            WheelDown(row, col, count) => f(row, col).map(|(r, c)| WheelDown(r, c, count))?,
+            Mouse(event) => {
+                f(event.row, event.col).map(|(row, col)| Mouse(MouseEvent { row, col, ..event }))?
+            }
+            key => key,
+        };
+        Some(key)
+    }
+}
+
+impl From<KeyEvent> for Key {
+    /// Convert to the legacy key if possible, otherwise `Key::Extended` is returned
+    #[rustfmt::skip]
+    fn from(event: KeyEvent) -> Self {
+        use self::Key::*;
+        if event.kind != KeyEventKind::Press {
+            return Extended(event);
+        }
+
+        // lock modifiers make no difference to legacy keys
+        let modifiers = event.modifiers - (KeyModifiers::CAPS_LOCK | KeyModifiers::NUM_LOCK);
+        let shift = KeyModifiers::SHIFT;
+        let alt = KeyModifiers::ALT;
+        let ctrl = KeyModifiers::CTRL;
+        let none = KeyModifiers::empty();
+
+        match (event.code, modifiers) {
+            (KeyCode::Char(ch), m) if m == none || m == shift => Char(ch),
+            (KeyCode::Char(ch @ 'a'..='z'), m) | (KeyCode::Char(ch @ ' '), m) if m == ctrl => Ctrl(ch),
+            (KeyCode::Char(ch @ 'a'..='z'), m) if m == ctrl | alt => CtrlAlt(ch),
+            (KeyCode::Char(ch), m) if m == alt || m == alt | shift => Alt(ch),
+
+            (KeyCode::Enter, m) if m == none => Enter,
+            (KeyCode::Enter, m) if m == alt => AltEnter,
+            (KeyCode::Tab, m) if m == none => Tab,
+            (KeyCode::Tab, m) if m == shift => BackTab,
+            (KeyCode::Tab, m) if m == alt => AltTab,
+            (KeyCode::Tab, m) if m == alt | shift => AltBackTab,
+            (KeyCode::Backspace, m) if m == none => Backspace,
+            (KeyCode::Backspace, m) if m == alt => AltBackspace,
+            (KeyCode::Esc, m) if m == none => ESC,
+
+            (KeyCode::Up, m) if m == none => Up,
+            (KeyCode::Down, m) if m == none => Down,
+            (KeyCode::Left, m) if m == none => Left,
+            (KeyCode::Right, m) if m == none => Right,
+            (KeyCode::Up, m) if m == ctrl => CtrlUp,
+            (KeyCode::Down, m) if m == ctrl => CtrlDown,
+            (KeyCode::Left, m) if m == ctrl => CtrlLeft,
+            (KeyCode::Right, m) if m == ctrl => CtrlRight,
+            (KeyCode::Up, m) if m == shift => ShiftUp,
+            (KeyCode::Down, m) if m == shift => ShiftDown,
+            (KeyCode::Left, m) if m == shift => ShiftLeft,
+            (KeyCode::Right, m) if m == shift => ShiftRight,
+            (KeyCode::Up, m) if m == alt => AltUp,
+            (KeyCode::Down, m) if m == alt => AltDown,
+            (KeyCode::Left, m) if m == alt => AltLeft,
+            (KeyCode::Right, m) if m == alt => AltRight,
+            (KeyCode::Up, m) if m == alt | shift => AltShiftUp,
+            (KeyCode::Down, m) if m == alt | shift => AltShiftDown,
+            (KeyCode::Left, m) if m == alt | shift => AltShiftLeft,
+            (KeyCode::Right, m) if m == alt | shift => AltShiftRight,
+
+            (KeyCode::Home, m) if m == none => Home,
+            (KeyCode::End, m) if m == none => End,
+            (KeyCode::Insert, m) if m == none => Insert,
+            (KeyCode::Delete, m) if m == none => Delete,
+            (KeyCode::PageUp, m) if m == none => PageUp,
+            (KeyCode::PageDown, m) if m == none => PageDown,
+            (KeyCode::Home, m) if m == alt => AltHome,
+            (KeyCode::End, m) if m == alt => AltEnd,
+            (KeyCode::PageUp, m) if m == alt => AltPageUp,
+            (KeyCode::PageDown, m) if m == alt => AltPageDown,
+
+            (KeyCode::F(n), m) if m == none => F(n),
+            _ => Extended(event),
+        }
+    }
+}
+
 #[rustfmt::skip]
 pub fn from_keyname(keyname: &str) -> Option<Key> {
     use self::Key::*;
@@ -266,8 +647,65 @@ pub fn from_keyname(keyname: &str) -> Option<Key> {
         ch if ch.chars().count() == 1 => {
             Some(Char(ch.chars().next().expect("input:parse_key: no key is specified")))
         },
-        _ => None,
+        keyname => parse_modified_keyname(keyname).map(Key::from),
+    }
+}
+
+/// parse the key names with arbitrary modifiers, e.g. `ctrl-shift-left`, `shift-f5`
+/// the key name should be lower case.
+fn parse_modified_keyname(keyname: &str) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::empty();
+    let mut name = keyname;
+    loop {
+        let (modifier, rest) = match name.find('-') {
+            Some(pos) if pos + 1 < name.len() => (&name[..pos], &name[pos + 1..]),
+            _ => break,
+        };
+        modifiers |= match modifier {
+            "ctrl" => KeyModifiers::CTRL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            "super" => KeyModifiers::SUPER,
+            "hyper" => KeyModifiers::HYPER,
+            "meta" => KeyModifiers::META,
+            _ => break,
+        };
+        name = rest;
+    }
+
+    if modifiers.is_empty() {
+        return None;
     }
+
+    let code = match name {
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "enter" | "return" => KeyCode::Enter,
+        "bspace" | "bs" => KeyCode::Backspace,
+        "ins" | "insert" => KeyCode::Insert,
+        "del" => KeyCode::Delete,
+        "pgup" | "page-up" => KeyCode::PageUp,
+        "pgdn" | "page-down" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "space" => KeyCode::Char(' '),
+        f if f.starts_with('f') && f.len() > 1 => KeyCode::F(f[1..].parse().ok()?),
+        ch if ch.chars().count() == 1 => {
+            let ch = ch.chars().next()?;
+            if modifiers.contains(KeyModifiers::SHIFT) {
+                KeyCode::Char(ch.to_ascii_uppercase())
+            } else {
+                KeyCode::Char(ch)
+            }
+        }
+        _ => return None,
+    };
+
+    Some(KeyEvent::new(code, modifiers))
 }
 
 #[cfg(test)]
@@ -283,4 +721,62 @@ mod test {
         // A correct way to refer to an uppercase char.
         assert_eq!(from_keyname("shift-a").unwrap(), Char('A'));
     }
+
+    #[test]
+    fn bind_modified_key() {
+        assert_eq!(
+            from_keyname("ctrl-alt-up"),
+            Some(Extended(KeyEvent::new(
+                KeyCode::Up,
+                KeyModifiers::CTRL | KeyModifiers::ALT
+            )))
+        );
+        assert_eq!(
+            from_keyname("shift-F5"),
+            Some(Extended(KeyEvent::new(KeyCode::F(5), KeyModifiers::SHIFT)))
+        );
+        assert_eq!(
+            from_keyname("ctrl-shift-x"),
+            Some(Extended(KeyEvent::new(
+                KeyCode::Char('X'),
+                KeyModifiers::CTRL | KeyModifiers::SHIFT
+            )))
+        );
+        // fall back to legacy keys if possible
+        assert_eq!(from_keyname("alt-shift-tab"), Some(AltBackTab));
+        assert_eq!(from_keyname("alt-page-down"), Some(AltPageDown));
+        assert_eq!(
+            from_keyname("alt-shift-home").map(|key| key.to_key_event()),
+            Some(Some(KeyEvent::new(
+                KeyCode::Home,
+                KeyModifiers::ALT | KeyModifiers::SHIFT
+            )))
+        );
+        assert_eq!(from_keyname("ctrl-"), None);
+        assert_eq!(from_keyname("foo-a"), None);
+    }
+
+    #[test]
+    fn key_event_round_trip() {
+        let keys = [
+            ESC,
+            Ctrl('a'),
+            Tab,
+            Enter,
+            BackTab,
+            AltBackTab,
+            CtrlLeft,
+            ShiftDown,
+            AltShiftRight,
+            AltPageUp,
+            F(3),
+            CtrlAlt('x'),
+            AltEnter,
+            Alt('Q'),
+            Char('q'),
+        ];
+        for &key in keys.iter() {
+            assert_eq!(key, Key::from(key.to_key_event().unwrap()));
+        }
+    }
 }
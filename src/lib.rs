@@ -58,6 +58,7 @@
 //! }
 //! ```
 pub mod attr;
+mod base64;
 pub mod canvas;
 pub mod cell;
 mod color;
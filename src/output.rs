@@ -13,13 +13,18 @@
 //!
 //! ```
 
+use std::collections::HashMap;
+use std::env;
 use std::io;
 use std::io::Write;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 
-use crate::attr::{Attr, Color, Effect};
+use crate::attr::{Attr, Color, ColorDepth, Effect};
+use crate::base64;
+use crate::cell::Hyperlink;
 use crate::sys::size::terminal_size;
 
+use bitflags::bitflags;
 use term::terminfo::parm::{expand, Param, Variables};
 use term::terminfo::TermInfo;
 
@@ -32,9 +37,15 @@ const DEFAULT_BUFFER_SIZE: usize = 1024;
 pub struct Output {
     /// A callable which returns the `Size` of the output terminal.
     buffer: Vec<u8>,
-    stdout: Box<dyn WriteAndAsRawFdAndSend>,
+    stdout: Box<dyn Write + Send>,
+    /// The raw fd of stdout, used to query the terminal size. `None` for headless output.
+    fd: Option<RawFd>,
+    /// The fixed size(width, height) of a headless output.
+    size: (usize, usize),
     /// The terminal environment variable. (xterm, xterm-256color, linux, ...)
     terminfo: TermInfo,
+    /// Colors beyond the depth will be downgraded.
+    color_depth: ColorDepth,
 }
 
 pub trait WriteAndAsRawFdAndSend: Write + AsRawFd + Send {}
@@ -43,17 +54,69 @@ impl<T> WriteAndAsRawFdAndSend for T where T: Write + AsRawFd + Send {}
 
 impl Output {
     pub fn new(stdout: Box<dyn WriteAndAsRawFdAndSend>) -> io::Result<Self> {
+        let terminfo = TermInfo::from_env()?;
+        let color_depth = detect_color_depth(
+            &terminfo,
+            env::var("NO_COLOR").ok(),
+            env::var("COLORTERM").ok(),
+        );
         Result::Ok(Self {
             buffer: Vec::with_capacity(DEFAULT_BUFFER_SIZE),
-            stdout,
-            terminfo: TermInfo::from_env()?,
+            fd: Some(stdout.as_raw_fd()),
+            stdout: Box::new(stdout),
+            size: (0, 0),
+            terminfo,
+            color_depth,
         })
     }
 
+    /// Create an output that is not attached to any terminal, mainly for testing.
+    ///
+    /// The terminal size is fixed to `(width, height)` and the escape codes are generated from
+    /// a built-in `xterm-256color` terminfo so that they won't depend on the environment, thus
+    /// RGB colors are downgraded to the 256 color palette unless `set_color_depth` says otherwise.
+    ///
+    /// ```
+    /// use std::io;
+    /// use tuikit::output::Output;
+    ///
+    /// let output = Output::headless(Box::new(io::sink()), 80, 24);
+    /// assert_eq!((80, 24), output.terminal_size().unwrap());
+    /// ```
+    pub fn headless(stdout: Box<dyn Write + Send>, width: usize, height: usize) -> Self {
+        Self {
+            buffer: Vec::with_capacity(DEFAULT_BUFFER_SIZE),
+            stdout,
+            fd: None,
+            size: (width, height),
+            terminfo: headless_terminfo(),
+            color_depth: ColorDepth::Ansi256,
+        }
+    }
+
+    /// The color depth of the terminal, detected from `NO_COLOR`, `COLORTERM` and the terminfo.
+    pub fn color_depth(&self) -> ColorDepth {
+        self.color_depth
+    }
+
+    /// Override the detected color depth, colors beyond it are mapped to the nearest one.
+    pub fn set_color_depth(&mut self, color_depth: ColorDepth) {
+        self.color_depth = color_depth;
+    }
+
     fn write_cap(&mut self, cmd: &str) {
         self.write_cap_with_params(cmd, &[])
     }
 
+    /// write the capability, or `fallback` if the terminfo doesn't have it.
+    fn write_cap_or(&mut self, cap: &str, fallback: &str) {
+        if self.terminfo.strings.contains_key(cap) {
+            self.write_cap(cap);
+        } else {
+            self.write_raw(fallback.as_bytes());
+        }
+    }
+
     fn write_cap_with_params(&mut self, cap: &str, params: &[Param]) {
         if let Some(cmd) = self.terminfo.strings.get(cap) {
             if let Ok(s) = expand(cmd, params, &mut Variables::new()) {
@@ -92,11 +155,55 @@ impl Output {
         self.write_raw(format!("\x1b]2;{}\x07", title).as_bytes());
     }
 
+    /// Start a hyperlink (OSC 8), the following contents are linked to it till the link is closed
+    /// by `None`.
+    /// Refer to: https://gist.github.com/egmontkob/eb114294efbcd5adb1944c9f3cb5feda
+    pub fn set_hyperlink(&mut self, link: Option<Hyperlink>) {
+        let sanitize = |s: &str| s.chars().filter(|ch| !ch.is_control()).collect::<String>();
+        match link {
+            Some(link) => {
+                let params = link
+                    .get_id()
+                    .map(|id| format!("id={}", sanitize(id).replace([':', ';'], "")))
+                    .unwrap_or_default();
+                let uri = sanitize(link.uri());
+                self.write_raw(format!("\x1b]8;{};{}\x1b\\", params, uri).as_bytes());
+            }
+            None => {
+                self.write_raw("\x1b]8;;\x1b\\".as_bytes());
+            }
+        }
+    }
+
+    /// Set the contents of the clipboard (OSC 52). As the terminal does the work, it works over
+    /// SSH as well. Some terminals limit the size of the contents or disable it by default.
+    pub fn set_clipboard(&mut self, selection: ClipboardSelection, content: &str) {
+        let encoded = base64::encode(content.as_bytes());
+        self.write_raw(format!("\x1b]52;{};{}\x1b\\", selection.param(), encoded).as_bytes());
+    }
+
+    /// Asks for the contents of the clipboard (OSC 52), the terminal will respond with
+    /// `ESC ] 52 ; selection ; base64 ST` if the query is supported and allowed.
+    pub fn request_clipboard(&mut self, selection: ClipboardSelection) {
+        self.write_raw(format!("\x1b]52;{};?\x1b\\", selection.param()).as_bytes());
+        self.flush()
+    }
+
     /// Clear title again. (or restore previous title.)
     pub fn clear_title(&mut self) {
         self.set_title("");
     }
 
+    /// Take the buffered contents out instead of writing them to the output stream.
+    pub(crate) fn take_buffer(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buffer)
+    }
+
+    /// the file descriptor of the output stream, `None` for headless outputs
+    pub(crate) fn raw_fd(&self) -> Option<RawFd> {
+        self.fd
+    }
+
     /// Write to output stream and flush.
     pub fn flush(&mut self) {
         let _ = self.stdout.write(&self.buffer);
@@ -121,7 +228,16 @@ impl Output {
 
     /// Enable mouse.
     pub fn enable_mouse_support(&mut self) {
-        self.write_raw("\x1b[?1000h".as_bytes());
+        self.enable_mouse_tracking(MouseTracking::Click);
+    }
+
+    /// Enable mouse, reporting the events according to `tracking`.
+    pub fn enable_mouse_tracking(&mut self, tracking: MouseTracking) {
+        match tracking {
+            MouseTracking::Click => self.write_raw("\x1b[?1000h".as_bytes()),
+            MouseTracking::Drag => self.write_raw("\x1b[?1002h".as_bytes()),
+            MouseTracking::Any => self.write_raw("\x1b[?1003h".as_bytes()),
+        }
 
         // Enable urxvt Mouse mode. (For terminals that understand this.)
         self.write_raw("\x1b[?1015h".as_bytes());
@@ -135,10 +251,25 @@ impl Output {
     /// Disable mouse.
     pub fn disable_mouse_support(&mut self) {
         self.write_raw("\x1b[?1000l".as_bytes());
+        self.write_raw("\x1b[?1002l".as_bytes());
+        self.write_raw("\x1b[?1003l".as_bytes());
         self.write_raw("\x1b[?1015l".as_bytes());
         self.write_raw("\x1b[?1006l".as_bytes());
     }
 
+    /// Enable the kitty keyboard protocol with `flags`, the previous flags are pushed to the stack
+    /// of the terminal.
+    /// Refer to: https://sw.kovidgoyal.net/kitty/keyboard-protocol/
+    pub fn enable_kitty_keyboard(&mut self, flags: KeyboardEnhancementFlags) {
+        self.write_raw(format!("\x1b[>{}u", flags.bits()).as_bytes());
+    }
+
+    /// Disable the kitty keyboard protocol, i.e. restore the flags pushed by
+    /// `enable_kitty_keyboard`.
+    pub fn disable_kitty_keyboard(&mut self) {
+        self.write_raw("\x1b[<u".as_bytes());
+    }
+
     /// Erases from the current cursor position to the end of the current line.
     pub fn erase_end_of_line(&mut self) {
         self.write_cap("el");
@@ -156,7 +287,7 @@ impl Output {
 
     /// Set current foreground color
     pub fn set_fg(&mut self, color: Color) {
-        match color {
+        match color.downgrade(self.color_depth) {
             Color::Default => {
                 self.write_raw("\x1b[39m".as_bytes());
             }
@@ -172,7 +303,7 @@ impl Output {
 
     /// Set current background color
     pub fn set_bg(&mut self, color: Color) {
-        match color {
+        match color.downgrade(self.color_depth) {
             Color::Default => {
                 self.write_raw("\x1b[49m".as_bytes());
             }
@@ -203,6 +334,48 @@ impl Output {
         if effect.contains(Effect::REVERSE) {
             self.write_cap("rev");
         }
+        if effect.contains(Effect::ITALIC) {
+            self.write_cap_or("sitm", "\x1b[3m");
+        }
+        if effect.contains(Effect::STRIKETHROUGH) {
+            self.write_raw("\x1b[9m".as_bytes());
+        }
+        if effect.contains(Effect::HIDDEN) {
+            self.write_cap_or("invis", "\x1b[8m");
+        }
+        if effect.contains(Effect::OVERLINE) {
+            self.write_raw("\x1b[53m".as_bytes());
+        }
+
+        // extended underline styles, refer to: https://sw.kovidgoyal.net/kitty/underlines/
+        if effect.contains(Effect::DOUBLE_UNDERLINE) {
+            self.write_raw("\x1b[4:2m".as_bytes());
+        }
+        if effect.contains(Effect::CURLY_UNDERLINE) {
+            self.write_raw("\x1b[4:3m".as_bytes());
+        }
+        if effect.contains(Effect::DOTTED_UNDERLINE) {
+            self.write_raw("\x1b[4:4m".as_bytes());
+        }
+        if effect.contains(Effect::DASHED_UNDERLINE) {
+            self.write_raw("\x1b[4:5m".as_bytes());
+        }
+    }
+
+    /// Set the color of underlines, `Color::Default` to use the foreground color.
+    pub fn set_underline_color(&mut self, color: Color) {
+        match color.downgrade(self.color_depth) {
+            Color::Default => {
+                self.write_raw("\x1b[59m".as_bytes());
+            }
+            Color::AnsiValue(x) => {
+                self.write_raw(format!("\x1b[58;5;{}m", x).as_bytes());
+            }
+            Color::Rgb(r, g, b) => {
+                self.write_raw(format!("\x1b[58;2;{};{};{}m", r, g, b).as_bytes());
+            }
+            Color::__Nonexhaustive => unreachable!(),
+        }
     }
 
     /// Set new color and styling attributes.
@@ -210,6 +383,103 @@ impl Output {
         self.set_fg(attr.fg);
         self.set_bg(attr.bg);
         self.set_effect(attr.effect);
+        // not all terminals understand the sequence, only emit it when necessary.
+        if attr.underline_color != Color::Default {
+            self.set_underline_color(attr.underline_color);
+        }
+    }
+
+    /// Change the attributes from `from`, i.e. the current ones, to `to` with a single SGR
+    /// sequence that contains only the differences, or resets everything first if that is
+    /// shorter.
+    pub fn change_attribute(&mut self, from: Attr, to: Attr) {
+        let delta = self.sgr_delta(from, to);
+        let mut reset = vec!["0".to_string()];
+        reset.extend(self.sgr_delta(Attr::default(), to));
+
+        let params = if reset.join(";").len() < delta.join(";").len() {
+            reset
+        } else {
+            delta
+        };
+        if !params.is_empty() {
+            self.write_raw(format!("\x1b[{}m", params.join(";")).as_bytes());
+        }
+    }
+
+    /// the SGR parameters that change the attributes from `from` to `to`
+    fn sgr_delta(&self, from: Attr, to: Attr) -> Vec<String> {
+        const BOLD_OR_DIM: Effect =
+            Effect::from_bits_truncate(Effect::BOLD.bits() | Effect::DIM.bits());
+        const UNDERLINES: Effect = Effect::from_bits_truncate(
+            Effect::UNDERLINE.bits()
+                | Effect::DOUBLE_UNDERLINE.bits()
+                | Effect::CURLY_UNDERLINE.bits()
+                | Effect::DOTTED_UNDERLINE.bits()
+                | Effect::DASHED_UNDERLINE.bits(),
+        );
+        let mut params = Vec::new();
+
+        // effects that are turned off, bold/dim and the underline styles are reset together
+        let removed = from.effect - to.effect;
+        let mut added = to.effect - from.effect;
+        if removed.intersects(BOLD_OR_DIM) {
+            params.push("22");
+            added |= to.effect & BOLD_OR_DIM;
+        }
+        if removed.intersects(UNDERLINES) {
+            params.push("24");
+            added |= to.effect & UNDERLINES;
+        }
+        let resets = [
+            (Effect::BLINK, "25"),
+            (Effect::REVERSE, "27"),
+            (Effect::ITALIC, "23"),
+            (Effect::STRIKETHROUGH, "29"),
+            (Effect::HIDDEN, "28"),
+            (Effect::OVERLINE, "55"),
+        ];
+        for &(effect, param) in resets.iter() {
+            if removed.contains(effect) {
+                params.push(param);
+            }
+        }
+
+        let sets = [
+            (Effect::BOLD, "1"),
+            (Effect::DIM, "2"),
+            (Effect::UNDERLINE, "4"),
+            (Effect::BLINK, "5"),
+            (Effect::REVERSE, "7"),
+            (Effect::ITALIC, "3"),
+            (Effect::STRIKETHROUGH, "9"),
+            (Effect::HIDDEN, "8"),
+            (Effect::OVERLINE, "53"),
+            (Effect::DOUBLE_UNDERLINE, "4:2"),
+            (Effect::CURLY_UNDERLINE, "4:3"),
+            (Effect::DOTTED_UNDERLINE, "4:4"),
+            (Effect::DASHED_UNDERLINE, "4:5"),
+        ];
+        for &(effect, param) in sets.iter() {
+            if added.contains(effect) {
+                params.push(param);
+            }
+        }
+        let mut params: Vec<String> = params.into_iter().map(String::from).collect();
+
+        let depth = self.color_depth;
+        let colors = [
+            (from.fg, to.fg, 30),
+            (from.bg, to.bg, 40),
+            (from.underline_color, to.underline_color, 50),
+        ];
+        for &(from, to, base) in colors.iter() {
+            let to = to.downgrade(depth);
+            if from.downgrade(depth) != to {
+                params.push(sgr_color(to, base));
+            }
+        }
+        params
     }
 
     /// Disable auto line wrapping.
@@ -230,6 +500,78 @@ impl Output {
         );
     }
 
+    /// Restrict scrolling to the rows `top..=bottom` (DECSTBM). The cursor is moved to home.
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        if self.terminfo.strings.contains_key("csr") {
+            self.write_cap_with_params(
+                "csr",
+                &[Param::Number(top as i32), Param::Number(bottom as i32)],
+            );
+        } else {
+            self.write_raw(format!("\x1b[{};{}r", top + 1, bottom + 1).as_bytes());
+        }
+    }
+
+    /// Let the whole screen scroll again. The cursor is moved to home.
+    pub fn reset_scroll_region(&mut self) {
+        self.write_raw(b"\x1b[r");
+    }
+
+    /// Scroll the contents of the scroll region up `amount` lines (SU), blank lines are
+    /// inserted at the bottom.
+    pub fn scroll_up(&mut self, amount: usize) {
+        if amount == 0 {
+            return;
+        }
+        if self.terminfo.strings.contains_key("indn") {
+            self.write_cap_with_params("indn", &[Param::Number(amount as i32)]);
+        } else {
+            self.write_raw(format!("\x1b[{}S", amount).as_bytes());
+        }
+    }
+
+    /// Scroll the contents of the scroll region down `amount` lines (SD), blank lines are
+    /// inserted at the top.
+    pub fn scroll_down(&mut self, amount: usize) {
+        if amount == 0 {
+            return;
+        }
+        if self.terminfo.strings.contains_key("rin") {
+            self.write_cap_with_params("rin", &[Param::Number(amount as i32)]);
+        } else {
+            self.write_raw(format!("\x1b[{}T", amount).as_bytes());
+        }
+    }
+
+    /// Whether the terminal could repeat the preceding character (REP).
+    pub fn supports_repeat(&self) -> bool {
+        self.terminfo.strings.contains_key("rep")
+    }
+
+    /// Whether the terminal could erase characters (ECH) with the current background color.
+    pub fn supports_erase_chars(&self) -> bool {
+        self.terminfo.strings.contains_key("ech")
+            && self.terminfo.bools.get("bce").cloned().unwrap_or(false)
+    }
+
+    /// Write `ch` `count` times, the repetitions are written with REP.
+    pub fn repeat_char(&mut self, ch: char, count: usize) {
+        if count == 0 {
+            return;
+        }
+        self.write(ch.to_string().as_str());
+        if count > 1 {
+            self.write_raw(format!("\x1b[{}b", count - 1).as_bytes());
+        }
+    }
+
+    /// Erase `count` characters from the cursor (ECH), the cursor is not moved.
+    pub fn erase_chars(&mut self, count: usize) {
+        if count > 0 {
+            self.write_cap_with_params("ech", &[Param::Number(count as i32)]);
+        }
+    }
+
     /// Move cursor `amount` place up.
     pub fn cursor_up(&mut self, amount: usize) {
         match amount {
@@ -276,12 +618,68 @@ impl Output {
         self.write_cap("cnorm");
     }
 
+    /// Set the shape (DECSCUSR) and color (OSC 12) of the cursor, `CursorStyle::default()`
+    /// restores the style configured by the user.
+    pub fn set_cursor_style(&mut self, style: CursorStyle) {
+        self.write_raw(format!("\x1b[{} q", style.decscusr_param()).as_bytes());
+        match style.color.to_rgb() {
+            Some((r, g, b)) => {
+                self.write_raw(format!("\x1b]12;#{:02x}{:02x}{:02x}\x1b\\", r, g, b).as_bytes())
+            }
+            None => self.write_raw(b"\x1b]112\x1b\\"),
+        }
+    }
+
     /// Asks for a cursor position report (CPR). (VT100 only.)
     pub fn ask_for_cpr(&mut self) {
         self.write_raw("\x1b[6n".as_bytes());
         self.flush()
     }
 
+    /// Asks for the state of a DEC private `mode` (DECRQM), the terminal will respond with
+    /// `ESC [ ? mode ; setting $ y` (DECRPM) if the query is supported.
+    pub fn request_mode(&mut self, mode: u16) {
+        self.write_raw(format!("\x1b[?{}$p", mode).as_bytes());
+        self.flush()
+    }
+
+    /// Asks for the primary device attributes (DA1), almost all terminals respond with
+    /// `ESC [ ? class ; attribute ; ... c`.
+    pub fn request_device_attributes(&mut self) {
+        self.write_raw("\x1b[c".as_bytes());
+        self.flush()
+    }
+
+    /// Asks for the name and version of the terminal (XTVERSION), the terminal will respond with
+    /// `ESC P > | text ESC \\` if the query is supported.
+    pub fn request_terminal_version(&mut self) {
+        self.write_raw("\x1b[>0q".as_bytes());
+        self.flush()
+    }
+
+    /// Asks for the current flags of the kitty keyboard protocol, the terminal will respond with
+    /// `ESC [ ? flags u` if the protocol is supported.
+    pub fn request_kitty_keyboard_flags(&mut self) {
+        self.write_raw("\x1b[?u".as_bytes());
+        self.flush()
+    }
+
+    /// Asks for the default foreground(OSC 10) and background(OSC 11) colors, the terminal will
+    /// respond with `ESC ] 10 ; rgb:rrrr/gggg/bbbb ST` and the like if the query is supported.
+    pub fn request_default_colors(&mut self) {
+        self.write_raw("\x1b]10;?\x1b\\\x1b]11;?\x1b\\".as_bytes());
+        self.flush()
+    }
+
+    /// Asks for the colors of the palette(OSC 4), the terminal will respond with
+    /// `ESC ] 4 ; index ; rgb:rrrr/gggg/bbbb ST` for each index if the query is supported.
+    pub fn request_palette_colors(&mut self, indices: &[u8]) {
+        for index in indices {
+            self.write_raw(format!("\x1b]4;{};?\x1b\\", index).as_bytes());
+        }
+        self.flush()
+    }
+
     /// Sound bell.
     pub fn bell(&mut self) {
         self.write_cap("bel");
@@ -290,7 +688,10 @@ impl Output {
 
     /// get terminal size (width, height)
     pub fn terminal_size(&self) -> io::Result<(usize, usize)> {
-        terminal_size(self.stdout.as_raw_fd())
+        match self.fd {
+            Some(fd) => terminal_size(fd),
+            None => Ok(self.size),
+        }
     }
 
     /// For vt100/xterm etc.
@@ -303,10 +704,35 @@ impl Output {
         self.write_raw("\x1b[?2004l".as_bytes());
     }
 
+    /// Enable focus events (DEC mode 1004), the terminal will report `ESC [ I` when it gains
+    /// focus and `ESC [ O` when it loses focus.
+    pub fn enable_focus_events(&mut self) {
+        self.write_raw("\x1b[?1004h".as_bytes());
+    }
+
+    /// Disable focus events (DEC mode 1004).
+    pub fn disable_focus_events(&mut self) {
+        self.write_raw("\x1b[?1004l".as_bytes());
+    }
+
+    /// Begin a synchronized update (DEC mode 2026), the terminal will hold the rendering until
+    /// the update ends, so that the frame won't be torn.
+    /// Refer to: https://gist.github.com/christianparpart/d8a62cc1ab659194337d73e399004036
+    pub fn begin_synchronized_update(&mut self) {
+        self.write_raw("\x1b[?2026h".as_bytes());
+    }
+
+    /// End a synchronized update (DEC mode 2026).
+    pub fn end_synchronized_update(&mut self) {
+        self.write_raw("\x1b[?2026l".as_bytes());
+    }
+
     ///  Execute the command
     pub fn execute(&mut self, cmd: Command) {
         match cmd {
             Command::PutChar(c) => self.write(c.to_string().as_str()),
+            Command::RepeatChar(ch, count) => self.repeat_char(ch, count),
+            Command::EraseChars(count) => self.erase_chars(count),
             Command::Write(content) => self.write(&content),
             Command::SetTitle(title) => self.set_title(&title),
             Command::ClearTitle => self.clear_title(),
@@ -326,13 +752,22 @@ impl Output {
                     self.disable_mouse_support();
                 }
             }
+            Command::KittyKeyboard(flags) => {
+                if flags.is_empty() {
+                    self.disable_kitty_keyboard();
+                } else {
+                    self.enable_kitty_keyboard(flags);
+                }
+            }
             Command::EraseEndOfLine => self.erase_end_of_line(),
             Command::EraseDown => self.erase_down(),
             Command::ResetAttributes => self.reset_attributes(),
             Command::Fg(fg) => self.set_fg(fg),
             Command::Bg(bg) => self.set_bg(bg),
             Command::Effect(effect) => self.set_effect(effect),
+            Command::UnderlineColor(color) => self.set_underline_color(color),
             Command::SetAttribute(attr) => self.set_attribute(attr),
+            Command::Hyperlink(link) => self.set_hyperlink(link),
             Command::AutoWrap(enable) => {
                 if enable {
                     self.enable_autowrap();
@@ -352,6 +787,13 @@ impl Output {
                     self.hide_cursor()
                 }
             }
+            Command::ChangeAttribute { from, to } => self.change_attribute(from, to),
+            Command::CursorNextLine => self.write_raw(b"\r\n"),
+            Command::CursorStyle(style) => self.set_cursor_style(style),
+            Command::SetScrollRegion { top, bottom } => self.set_scroll_region(top, bottom),
+            Command::ResetScrollRegion => self.reset_scroll_region(),
+            Command::ScrollUp(amount) => self.scroll_up(amount),
+            Command::ScrollDown(amount) => self.scroll_down(amount),
             Command::BracketedPaste(enable) => {
                 if enable {
                     self.enable_bracketed_paste()
@@ -359,15 +801,238 @@ impl Output {
                     self.disable_bracketed_paste()
                 }
             }
+            Command::FocusEvents(enable) => {
+                if enable {
+                    self.enable_focus_events()
+                } else {
+                    self.disable_focus_events()
+                }
+            }
+            Command::SynchronizedUpdate(begin) => {
+                if begin {
+                    self.begin_synchronized_update()
+                } else {
+                    self.end_synchronized_update()
+                }
+            }
+        }
+    }
+}
+
+/// The selection OSC 52 operates on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ClipboardSelection {
+    /// The system clipboard
+    Clipboard,
+    /// The primary selection (X11), i.e. the selected text
+    Primary,
+}
+
+impl ClipboardSelection {
+    fn param(self) -> &'static str {
+        match self {
+            ClipboardSelection::Clipboard => "c",
+            ClipboardSelection::Primary => "p",
+        }
+    }
+
+    /// parse the selection parameter of OSC 52 replies, terminals may reply with an empty one
+    pub(crate) fn from_param(param: &str) -> Self {
+        if param.starts_with('p') {
+            ClipboardSelection::Primary
+        } else {
+            ClipboardSelection::Clipboard
         }
     }
 }
 
+/// The shape of the cursor, set with DECSCUSR.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CursorShape {
+    /// The shape configured by the user
+    Default,
+    Block,
+    Underline,
+    Bar,
+}
+
+impl Default for CursorShape {
+    fn default() -> Self {
+        CursorShape::Default
+    }
+}
+
+/// The style of the cursor: shape, blinking and color.
+///
+/// ```
+/// use tuikit::attr::Color;
+/// use tuikit::output::{CursorShape, CursorStyle};
+///
+/// let style = CursorStyle::new(CursorShape::Bar).blinking(true).color(Color::Rgb(255, 0, 0));
+/// assert_eq!(CursorShape::Bar, style.shape);
+/// assert_eq!(CursorStyle::default().shape, CursorShape::Default);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct CursorStyle {
+    pub shape: CursorShape,
+    pub blinking: bool,
+    /// the color of the cursor (OSC 12), `Color::Default` for the one configured by the user
+    pub color: Color,
+}
+
+impl CursorStyle {
+    pub fn new(shape: CursorShape) -> Self {
+        Self {
+            shape,
+            ..Self::default()
+        }
+    }
+
+    pub fn blinking(mut self, blinking: bool) -> Self {
+        self.blinking = blinking;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// the parameter of DECSCUSR: `CSI n SP q`
+    fn decscusr_param(self) -> u8 {
+        let steady = if self.blinking { 0 } else { 1 };
+        match self.shape {
+            CursorShape::Default => 0,
+            CursorShape::Block => 1 + steady,
+            CursorShape::Underline => 3 + steady,
+            CursorShape::Bar => 5 + steady,
+        }
+    }
+}
+
+/// Which mouse events are reported by the terminal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MouseTracking {
+    /// Button presses, releases and wheels (mode 1000)
+    Click,
+    /// Also motions while a button is pressed, i.e. `Key::MouseDrag` (mode 1002)
+    Drag,
+    /// Also motions without any button pressed, i.e. `Key::MouseMove` (mode 1003)
+    Any,
+}
+
+bitflags! {
+    /// Flags of the kitty keyboard protocol's progressive enhancement.
+    /// Refer to: https://sw.kovidgoyal.net/kitty/keyboard-protocol/#progressive-enhancement
+    pub struct KeyboardEnhancementFlags: u8 {
+        const DISAMBIGUATE_ESCAPE_CODES = 0b00000001;
+        const REPORT_EVENT_TYPES = 0b00000010;
+        const REPORT_ALTERNATE_KEYS = 0b00000100;
+        const REPORT_ALL_KEYS_AS_ESCAPE_CODES = 0b00001000;
+        const REPORT_ASSOCIATED_TEXT = 0b00010000;
+    }
+}
+
+/// the SGR parameter of a color, `base` is 30 for foreground, 40 for background and 50 for
+/// underline colors.
+fn sgr_color(color: Color, base: u8) -> String {
+    match color {
+        Color::AnsiValue(x) if x < 8 && base != 50 => (base + x).to_string(),
+        Color::AnsiValue(x) if x < 16 && base != 50 => (base + 60 + x - 8).to_string(),
+        Color::AnsiValue(x) => format!("{};5;{}", base + 8, x),
+        Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        _ => (base + 9).to_string(),
+    }
+}
+
+/// Detect the color depth according to https://no-color.org, the `COLORTERM` convention for
+/// true colors and the `colors` capability of the terminfo.
+fn detect_color_depth(
+    terminfo: &TermInfo,
+    no_color: Option<String>,
+    colorterm: Option<String>,
+) -> ColorDepth {
+    if no_color.map(|value| !value.is_empty()).unwrap_or(false) {
+        return ColorDepth::NoColor;
+    }
+
+    if let Some("truecolor") | Some("24bit") = colorterm.as_deref() {
+        return ColorDepth::TrueColor;
+    }
+
+    match terminfo.numbers.get("colors") {
+        Some(&colors) if colors >= 1 << 24 => ColorDepth::TrueColor,
+        Some(&colors) if colors >= 256 => ColorDepth::Ansi256,
+        Some(&colors) if colors >= 8 => ColorDepth::Ansi16,
+        _ => ColorDepth::NoColor,
+    }
+}
+
+/// The terminfo used by headless outputs, the capabilities are taken from `xterm-256color`
+fn headless_terminfo() -> TermInfo {
+    let strings: HashMap<&'static str, Vec<u8>> = [
+        ("clear", "\x1b[H\x1b[2J"),
+        ("smcup", "\x1b[?1049h"),
+        ("rmcup", "\x1b[?1049l"),
+        ("el", "\x1b[K"),
+        ("ed", "\x1b[J"),
+        ("sgr0", "\x1b(B\x1b[m"),
+        (
+            "setaf",
+            "\x1b[%?%p1%{8}%<%t3%p1%d%e%p1%{16}%<%t9%p1%{8}%-%d%e38;5;%p1%d%;m",
+        ),
+        (
+            "setab",
+            "\x1b[%?%p1%{8}%<%t4%p1%d%e%p1%{16}%<%t10%p1%{8}%-%d%e48;5;%p1%d%;m",
+        ),
+        ("bold", "\x1b[1m"),
+        ("dim", "\x1b[2m"),
+        ("smul", "\x1b[4m"),
+        ("blink", "\x1b[5m"),
+        ("rev", "\x1b[7m"),
+        ("sitm", "\x1b[3m"),
+        ("invis", "\x1b[8m"),
+        ("rmam", "\x1b[?7l"),
+        ("smam", "\x1b[?7h"),
+        ("cup", "\x1b[%i%p1%d;%p2%dH"),
+        ("cuu1", "\x1b[A"),
+        ("cuu", "\x1b[%p1%dA"),
+        ("cud1", "\n"),
+        ("cud", "\x1b[%p1%dB"),
+        ("cuf1", "\x1b[C"),
+        ("cuf", "\x1b[%p1%dC"),
+        ("cub1", "\x08"),
+        ("cub", "\x1b[%p1%dD"),
+        ("csr", "\x1b[%i%p1%d;%p2%dr"),
+        ("ech", "\x1b[%p1%dX"),
+        ("rep", "%p1%c\x1b[%p2%{1}%-%db"),
+        ("indn", "\x1b[%p1%dS"),
+        ("rin", "\x1b[%p1%dT"),
+        ("civis", "\x1b[?25l"),
+        ("cnorm", "\x1b[?12l\x1b[?25h"),
+        ("bel", "\x07"),
+    ]
+    .iter()
+    .map(|&(cap, value)| (cap, value.as_bytes().to_vec()))
+    .collect();
+
+    TermInfo {
+        names: vec!["xterm-256color".to_string()],
+        bools: [("bce", true)].iter().cloned().collect(),
+        numbers: [("colors", 256)].iter().cloned().collect(),
+        strings,
+    }
+}
+
 /// Instead of calling functions of `Output`, we could send commands.
 #[derive(Debug, Clone)]
 pub enum Command {
     /// Put a char to screen
     PutChar(char),
+    /// Put `char` `x` times, i.e. REP
+    RepeatChar(char, usize),
+    /// Erase `x` characters from the cursor, the cursor is not moved
+    EraseChars(usize),
     /// Write content to screen (escape codes will be escaped)
     Write(String),
     /// Set the title of the terminal
@@ -382,6 +1047,8 @@ pub enum Command {
     AlternateScreen(bool),
     /// Enable(true)/Disable(false) mouse support
     MouseSupport(bool),
+    /// Enable the kitty keyboard protocol with the flags, empty flags to disable it
+    KittyKeyboard(KeyboardEnhancementFlags),
     /// Erase contents to the end of current line
     EraseEndOfLine,
     /// Erase contents till the bottom of the screen
@@ -394,8 +1061,15 @@ pub enum Command {
     Bg(Color),
     /// Set the effect(e.g. underline, dim, bold, ...)
     Effect(Effect),
-    /// Set the fg, bg & effect.
+    /// Set the color of underlines
+    UnderlineColor(Color),
+    /// Set the fg, bg, effect & underline color.
     SetAttribute(Attr),
+    /// Change the attributes from the current ones `from` to `to`, only the differences are
+    /// written
+    ChangeAttribute { from: Attr, to: Attr },
+    /// Start a hyperlink(Some) or close the current one(None)
+    Hyperlink(Option<Hyperlink>),
     /// Enable(true)/Disable(false) autowrap
     AutoWrap(bool),
     /// move the cursor to `(row, col)`
@@ -408,8 +1082,212 @@ pub enum Command {
     CursorLeft(usize),
     /// move cursor right `x` characters
     CursorRight(usize),
+    /// move cursor to the beginning of the next line
+    CursorNextLine,
     /// Show(true)/Hide(false) cursor
     CursorShow(bool),
+    /// Change the shape and color of the cursor
+    CursorStyle(CursorStyle),
+    /// Restrict scrolling to the rows `top..=bottom`, the cursor is moved to home
+    SetScrollRegion { top: usize, bottom: usize },
+    /// Let the whole screen scroll, the cursor is moved to home
+    ResetScrollRegion,
+    /// scroll the contents of the scroll region up `x` lines
+    ScrollUp(usize),
+    /// scroll the contents of the scroll region down `x` lines
+    ScrollDown(usize),
     /// Enable(true)/Disable(false) the bracketed paste mode
     BracketedPaste(bool),
+    /// Enable(true)/Disable(false) focus events
+    FocusEvents(bool),
+    /// Begin(true)/End(false) a synchronized update, i.e. DEC mode 2026
+    SynchronizedUpdate(bool),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn render(attr: Attr) -> String {
+        let mut output = Output::headless(Box::new(io::sink()), 80, 24);
+        output.set_color_depth(ColorDepth::TrueColor);
+        output.set_attribute(attr);
+        String::from_utf8(output.take_buffer()).unwrap()
+    }
+
+    #[test]
+    fn test_extended_effects() {
+        let attr = Attr::default().effect(Effect::ITALIC | Effect::STRIKETHROUGH);
+        assert_eq!("\x1b[39m\x1b[49m\x1b[3m\x1b[9m", render(attr));
+
+        let attr = Attr::default().effect(Effect::HIDDEN | Effect::OVERLINE);
+        assert_eq!("\x1b[39m\x1b[49m\x1b[8m\x1b[53m", render(attr));
+
+        let attr = Attr::default()
+            .effect(Effect::CURLY_UNDERLINE)
+            .underline_color(Color::Rgb(255, 0, 0));
+        assert_eq!("\x1b[39m\x1b[49m\x1b[4:3m\x1b[58;2;255;0;0m", render(attr));
+
+        let attr = Attr::default()
+            .effect(Effect::DOUBLE_UNDERLINE)
+            .underline_color(Color::AnsiValue(1));
+        assert_eq!("\x1b[39m\x1b[49m\x1b[4:2m\x1b[58;5;1m", render(attr));
+    }
+
+    #[test]
+    fn test_hyperlink() {
+        let mut output = Output::headless(Box::new(io::sink()), 80, 24);
+        output.set_hyperlink(Some(Hyperlink::new("https://example.com/\x1b").unwrap()));
+        output.write("link");
+        output.set_hyperlink(None);
+        assert_eq!(
+            b"\x1b]8;;https://example.com/\x1b\\link\x1b]8;;\x1b\\".to_vec(),
+            output.take_buffer()
+        );
+
+        output.set_hyperlink(Some(
+            Hyperlink::new("file:///tmp").unwrap().id("a;b").unwrap(),
+        ));
+        assert_eq!(
+            b"\x1b]8;id=ab;file:///tmp\x1b\\".to_vec(),
+            output.take_buffer()
+        );
+    }
+
+    #[test]
+    fn test_detect_color_depth() {
+        let terminfo = headless_terminfo();
+        let detect = |no_color: Option<&str>, colorterm: Option<&str>| {
+            detect_color_depth(
+                &terminfo,
+                no_color.map(String::from),
+                colorterm.map(String::from),
+            )
+        };
+        assert_eq!(ColorDepth::Ansi256, detect(None, None));
+        assert_eq!(ColorDepth::Ansi256, detect(Some(""), None));
+        assert_eq!(ColorDepth::TrueColor, detect(None, Some("truecolor")));
+        assert_eq!(ColorDepth::NoColor, detect(Some("1"), Some("truecolor")));
+
+        let mut linux = headless_terminfo();
+        linux.numbers.insert("colors", 8);
+        assert_eq!(ColorDepth::Ansi16, detect_color_depth(&linux, None, None));
+        linux.numbers.clear();
+        assert_eq!(ColorDepth::NoColor, detect_color_depth(&linux, None, None));
+    }
+
+    #[test]
+    fn test_downgrade_colors() {
+        let mut output = Output::headless(Box::new(io::sink()), 80, 24);
+        output.set_fg(Color::Rgb(255, 0, 0));
+        assert_eq!(b"\x1b[38;5;196m".to_vec(), output.take_buffer());
+
+        output.set_color_depth(ColorDepth::Ansi16);
+        output.set_bg(Color::Rgb(255, 0, 0));
+        assert_eq!(b"\x1b[101m".to_vec(), output.take_buffer());
+
+        output.set_color_depth(ColorDepth::NoColor);
+        output.set_fg(Color::RED);
+        assert_eq!(b"\x1b[39m".to_vec(), output.take_buffer());
+    }
+
+    #[test]
+    fn test_clipboard() {
+        let mut output = Output::headless(Box::new(io::sink()), 80, 24);
+        output.set_clipboard(ClipboardSelection::Clipboard, "hello");
+        assert_eq!(b"\x1b]52;c;aGVsbG8=\x1b\\".to_vec(), output.take_buffer());
+    }
+
+    #[test]
+    fn test_cursor_style() {
+        let mut output = Output::headless(Box::new(io::sink()), 80, 24);
+        output.set_cursor_style(CursorStyle::new(CursorShape::Bar));
+        assert_eq!(b"\x1b[6 q\x1b]112\x1b\\".to_vec(), output.take_buffer());
+
+        let style = CursorStyle::new(CursorShape::Underline)
+            .blinking(true)
+            .color(Color::Rgb(255, 128, 0));
+        output.set_cursor_style(style);
+        assert_eq!(
+            b"\x1b[3 q\x1b]12;#ff8000\x1b\\".to_vec(),
+            output.take_buffer()
+        );
+
+        output.set_cursor_style(CursorStyle::default().color(Color::RED));
+        assert_eq!(
+            b"\x1b[0 q\x1b]12;#cd0000\x1b\\".to_vec(),
+            output.take_buffer()
+        );
+    }
+
+    #[test]
+    fn test_scroll_region() {
+        let mut output = Output::headless(Box::new(io::sink()), 80, 24);
+        output.execute(Command::SetScrollRegion { top: 2, bottom: 9 });
+        output.execute(Command::ScrollUp(3));
+        output.execute(Command::ScrollDown(0));
+        output.execute(Command::ResetScrollRegion);
+        let written = String::from_utf8(output.take_buffer()).unwrap();
+        assert_eq!("\x1b[3;10r\x1b[3S\x1b[r", written);
+    }
+
+    #[test]
+    fn test_change_attribute() {
+        let change = |from: Attr, to: Attr| {
+            let mut output = Output::headless(Box::new(io::sink()), 80, 24);
+            output.set_color_depth(ColorDepth::TrueColor);
+            output.change_attribute(from, to);
+            String::from_utf8(output.take_buffer()).unwrap()
+        };
+
+        let bold_red = Attr::from(Effect::BOLD).fg(Color::RED);
+        assert_eq!("", change(bold_red, bold_red));
+        assert_eq!("\x1b[1;31m", change(Attr::default(), bold_red));
+        assert_eq!(
+            "\x1b[34m",
+            change(bold_red, Attr::from(Effect::BOLD).fg(Color::BLUE))
+        );
+        assert_eq!(
+            "\x1b[48;2;1;2;3m",
+            change(bold_red, bold_red.bg(Color::Rgb(1, 2, 3)))
+        );
+
+        // bold and dim are turned off together
+        let bold_dim = Attr::from(Effect::BOLD | Effect::DIM).fg(Color::RED);
+        assert_eq!(
+            "\x1b[22;2m",
+            change(bold_dim, Attr::from(Effect::DIM).fg(Color::RED))
+        );
+        let curly = Attr::from(Effect::UNDERLINE | Effect::CURLY_UNDERLINE).fg(Color::RED);
+        assert_eq!(
+            "\x1b[24;4m",
+            change(curly, Attr::from(Effect::UNDERLINE).fg(Color::RED))
+        );
+
+        // reset everything if that is shorter
+        let fancy = Attr::from(Effect::BOLD | Effect::ITALIC | Effect::REVERSE)
+            .fg(Color::Rgb(10, 20, 30))
+            .bg(Color::AnsiValue(100));
+        assert_eq!("\x1b[0m", change(fancy, Attr::default()));
+        assert_eq!("\x1b[0;5m", change(fancy, Attr::from(Effect::BLINK)));
+
+        // colors are compared after they are downgraded
+        let mut output = Output::headless(Box::new(io::sink()), 80, 24);
+        output.set_color_depth(ColorDepth::NoColor);
+        output.change_attribute(Attr::default(), Attr::default().fg(Color::RED));
+        assert!(output.take_buffer().is_empty());
+    }
+
+    #[test]
+    fn test_runs() {
+        let mut output = Output::headless(Box::new(io::sink()), 80, 24);
+        assert!(output.supports_repeat());
+        assert!(output.supports_erase_chars());
+
+        output.execute(Command::RepeatChar('─', 10));
+        output.execute(Command::RepeatChar('a', 1));
+        output.execute(Command::EraseChars(20));
+        let written = String::from_utf8(output.take_buffer()).unwrap();
+        assert_eq!("─\x1b[9ba\x1b[20X", written);
+    }
 }
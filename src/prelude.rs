@@ -1,10 +1,11 @@
-pub use crate::attr::{Attr, Color, Effect};
+pub use crate::attr::{Attr, Color, ColorDepth, Effect};
 pub use crate::canvas::Canvas;
 pub use crate::cell::Cell;
 pub use crate::draw::{Draw, DrawResult};
 pub use crate::event::Event;
 pub use crate::key::*;
-pub use crate::term::{Term, TermHeight, TermOptions};
+pub use crate::output::{ClipboardSelection, CursorShape, CursorStyle};
+pub use crate::term::{SynchronizedOutput, Term, TermHeight, TermOptions, TerminalCapabilities};
 pub use crate::widget::{
     AlignSelf, HSplit, HorizontalAlign, Rectangle, Size, Split, Stack, VSplit, VerticalAlign,
     Widget, Win,
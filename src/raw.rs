@@ -48,6 +48,13 @@ pub struct RawTerminal<W: Write + AsRawFd> {
     output: W,
 }
 
+impl<W: Write + AsRawFd> RawTerminal<W> {
+    /// the state of the terminal before entering raw mode
+    pub(crate) fn prev_ios(&self) -> &Termios {
+        &self.prev_ios
+    }
+}
+
 impl<W: Write + AsRawFd> Drop for RawTerminal<W> {
     fn drop(&mut self) {
         let _ = tcsetattr(self.output.as_raw_fd(), SetArg::TCSANOW, &self.prev_ios);
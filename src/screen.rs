@@ -3,10 +3,12 @@ use crate::attr::Attr;
 use crate::canvas::Canvas;
 use crate::cell::Cell;
 use crate::error::TuikitError;
-use crate::output::Command;
+use crate::output::{Command, CursorStyle};
 use crate::Result;
 use std::cmp::{max, min};
-use unicode_width::UnicodeWidthChar;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
 // much of the code comes from https://github.com/agatan/termfest/blob/master/src/screen.rs
 
@@ -20,7 +22,11 @@ pub struct Screen {
     cells: Vec<Cell>,
     painted_cells: Vec<Cell>,
     painted_cursor: Cursor,
+    // the painted cells no longer reflect the terminal, e.g. after it is restored or resized
+    painted_unknown: bool,
     clear_on_start: bool,
+    use_repeat: bool,
+    use_erase_chars: bool,
 }
 
 impl Screen {
@@ -33,7 +39,10 @@ impl Screen {
             cursor: Cursor::default(),
             painted_cells: vec![Cell::default(); width * height],
             painted_cursor: Cursor::default(),
+            painted_unknown: false,
             clear_on_start: false,
+            use_repeat: false,
+            use_erase_chars: false,
         }
     }
 
@@ -41,6 +50,16 @@ impl Screen {
         self.clear_on_start = clear_on_start;
     }
 
+    /// write runs of identical chars with REP, only if the terminal supports it
+    pub fn use_repeat(&mut self, use_repeat: bool) {
+        self.use_repeat = use_repeat;
+    }
+
+    /// erase runs of blanks with ECH, only if the terminal supports it
+    pub fn use_erase_chars(&mut self, use_erase_chars: bool) {
+        self.use_erase_chars = use_erase_chars;
+    }
+
     /// get the width of the screen
     #[inline]
     pub fn width(&self) -> usize {
@@ -85,6 +104,7 @@ impl Screen {
     pub fn resize(&mut self, width: usize, height: usize) {
         self.cells = self.copy_cells(&self.cells, width, height);
         self.painted_cells = self.empty_canvas(width, height);
+        self.painted_unknown = true;
         self.width = width;
         self.height = height;
 
@@ -92,26 +112,60 @@ impl Screen {
         self.cursor.col = min(self.cursor.col, width);
     }
 
+    /// forget the painted contents, so that the next `present` will paint everything and erase
+    /// the trailing blanks of every row
+    pub fn invalidate(&mut self) {
+        self.painted_cells = self.empty_canvas(self.width, self.height);
+        self.painted_unknown = true;
+        self.painted_cursor.style = CursorStyle::default();
+    }
+
+    /// get the cursor style as it was painted by the last `present`
+    pub fn painted_cursor_style(&self) -> CursorStyle {
+        self.painted_cursor.style
+    }
+
     /// sync internal buffer with the terminal
     pub fn present(&mut self) -> Vec<Command> {
         let mut commands = Vec::with_capacity(2048);
         let default_attr = Attr::default();
         let mut last_attr = default_attr;
+        let mut last_link = None;
 
         // hide cursor && reset Attributes
         commands.push(Command::CursorShow(false));
         commands.push(Command::CursorGoto { row: 0, col: 0 });
         commands.push(Command::ResetAttributes);
 
+        // scroll the terminal if the contents are shifted vertically
+        let scroll = if self.painted_unknown {
+            None
+        } else {
+            self.detect_scroll()
+        };
+        if let Some((top, bottom, shift)) = scroll {
+            commands.push(Command::SetScrollRegion { top, bottom });
+            if shift > 0 {
+                commands.push(Command::ScrollUp(shift as usize));
+            } else {
+                commands.push(Command::ScrollDown(-shift as usize));
+            }
+            commands.push(Command::ResetScrollRegion);
+            commands.push(Command::CursorGoto { row: 0, col: 0 });
+            self.scroll_painted(top, bottom, shift);
+        }
+
         let mut last_cursor = Cursor::default();
 
         for row in 0..self.height {
             // calculate the last col that has contents
             let mut empty_col_index = 0;
+            let mut trailing_changed = self.painted_unknown;
             for col in (0..self.width).rev() {
                 let index = self.index(row, col).unwrap();
                 let cell = &self.cells[index];
                 if cell.is_empty() {
+                    trailing_changed |= self.painted_cells[index] != *cell;
                     self.painted_cells[index] = *cell;
                 } else {
                     empty_col_index = col + 1;
@@ -120,13 +174,13 @@ impl Screen {
             }
 
             // compare cells and print necessary escape codes
-            let mut last_ch_is_wide = false;
+            let mut cols_to_skip = 0;
             for col in 0..empty_col_index {
                 let index = self.index(row, col).unwrap();
 
                 // advance if the last character is wide
-                if last_ch_is_wide {
-                    last_ch_is_wide = false;
+                if cols_to_skip > 0 {
+                    cols_to_skip -= 1;
                     self.painted_cells[index] = self.cells[index];
                     continue;
                 }
@@ -141,30 +195,61 @@ impl Screen {
 
                 // move cursor if necessary
                 if last_cursor.row != row || last_cursor.col != col {
-                    commands.push(Command::CursorGoto { row, col });
+                    commands.push(cursor_move(last_cursor, row, col));
                 }
 
                 if cell_to_paint.attr != last_attr {
-                    commands.push(Command::ResetAttributes);
-                    commands.push(Command::SetAttribute(cell_to_paint.attr));
+                    commands.push(Command::ChangeAttribute {
+                        from: last_attr,
+                        to: cell_to_paint.attr,
+                    });
                     last_attr = cell_to_paint.attr;
                 }
 
+                if cell_to_paint.link != last_link {
+                    commands.push(Command::Hyperlink(cell_to_paint.link));
+                    last_link = cell_to_paint.link;
+                }
+
+                // paint a run of identical cells at once if possible
+                if self.use_repeat || self.use_erase_chars {
+                    let run_end = (col..empty_col_index)
+                        .find(|&c| self.cells[index - col + c] != cell_to_paint)
+                        .unwrap_or(empty_col_index);
+                    if let Some(command) = self.run_command(cell_to_paint, run_end - col) {
+                        // the cursor is not moved by erasing
+                        let erased = matches!(command, Command::EraseChars(_));
+                        commands.push(command);
+                        cols_to_skip = run_end - col - 1;
+                        last_cursor.row = row;
+                        last_cursor.col = if erased { col } else { run_end };
+                        self.painted_cells[index] = cell_to_paint;
+                        continue;
+                    }
+                }
+
                 // correctly draw the characters
-                match cell_to_paint.ch {
-                    '\n' | '\r' | '\t' | '\0' => {
+                match cell_to_paint.ch.as_char() {
+                    Some('\n') | Some('\r') | Some('\t') | Some('\0') => {
+                        commands.push(Command::PutChar(' '));
+                    }
+                    Some(ch) => {
+                        commands.push(Command::PutChar(ch));
+                    }
+                    None if cell_to_paint.ch.as_str().chars().any(char::is_control) => {
+                        // e.g. "\r\n" is a single grapheme cluster
                         commands.push(Command::PutChar(' '));
                     }
-                    _ => {
-                        commands.push(Command::PutChar(cell_to_paint.ch));
+                    None => {
+                        commands.push(Command::Write(cell_to_paint.ch.to_string()));
                     }
                 }
 
-                let display_width = cell_to_paint.ch.width().unwrap_or(2);
+                let display_width = cell_to_paint.ch.width();
 
                 // wide character
-                if display_width == 2 {
-                    last_ch_is_wide = true;
+                if display_width > 1 {
+                    cols_to_skip = display_width - 1;
                 }
 
                 last_cursor.row = row;
@@ -172,7 +257,16 @@ impl Screen {
                 self.painted_cells[index] = cell_to_paint;
             }
 
-            if empty_col_index != self.width {
+            // close the link at the end of the line so that it won't leak into the erased area
+            if last_link.is_some() {
+                commands.push(Command::Hyperlink(None));
+                last_link = None;
+            }
+
+            // the trailing blanks are erased only if they are not blank already, otherwise every
+            // frame would write a goto, a reset and an erase for each row that is not full. All
+            // rows are erased if the painted contents are unknown.
+            if empty_col_index != self.width && trailing_changed {
                 commands.push(Command::CursorGoto {
                     row,
                     col: empty_col_index,
@@ -182,6 +276,8 @@ impl Screen {
                     commands.push(Command::EraseEndOfLine);
                 }
                 last_attr = Attr::default();
+                last_cursor.row = row;
+                last_cursor.col = empty_col_index;
             }
         }
 
@@ -190,15 +286,155 @@ impl Screen {
             row: self.cursor.row,
             col: self.cursor.col,
         });
+        if self.cursor.style != self.painted_cursor.style {
+            commands.push(Command::CursorStyle(self.cursor.style));
+        }
         if self.cursor.visible {
             commands.push(Command::CursorShow(true));
         }
 
         self.painted_cursor = self.cursor;
+        self.painted_unknown = false;
 
         commands
     }
 
+    /// the command that paints `count` cells of `cell` at once: erase(ECH) blanks and
+    /// repeat(REP) other chars. return `None` if it is not shorter than painting one by one.
+    fn run_command(&self, cell: Cell, count: usize) -> Option<Command> {
+        if count < 2 || cell.link.is_some() || cell.ch.width() != 1 {
+            return None;
+        }
+        let ch = cell.ch.as_char().filter(|ch| !ch.is_control())?;
+
+        // erased cells only have the background color, the cursor needs to be moved afterwards
+        let erase_len = 3 + digits(count);
+        if self.use_erase_chars && ch == ' ' && cell.attr.effect.is_empty() && count > 2 * erase_len
+        {
+            return Some(Command::EraseChars(count));
+        }
+
+        let repeat_len = 3 + digits(count - 1);
+        if self.use_repeat && (count - 1) * ch.len_utf8() > repeat_len {
+            return Some(Command::RepeatChar(ch, count));
+        }
+        None
+    }
+
+    #[inline]
+    fn row_matches(&self, row: usize, painted_row: usize) -> bool {
+        let width = self.width;
+        self.cells[row * width..(row + 1) * width]
+            == self.painted_cells[painted_row * width..(painted_row + 1) * width]
+    }
+
+    /// Detect whether the contents between the first and the last changed rows are shifted
+    /// vertically, e.g. a list scrolled by a line, so that scrolling the terminal would save
+    /// repainting the shifted rows.
+    ///
+    /// return `(top, bottom, shift)`: rows `top..=bottom` are scrolled up `shift` lines, or
+    /// down if `shift` is negative.
+    fn detect_scroll(&self) -> Option<(usize, usize, isize)> {
+        let changed = |row: &usize| !self.row_matches(*row, *row);
+        let top = (0..self.height).find(changed)?;
+        let bottom = (0..self.height).rev().find(changed)?;
+        let height = bottom - top + 1;
+
+        // rows that are already painted
+        let matched = (top..=bottom).filter(|row| !changed(row)).count();
+
+        // hash the rows once, only the shifts that move some painted row to a row of the same
+        // contents are tried, instead of comparing whole rows for every possible shift.
+        let width = self.width;
+        let hash_row = |cells: &[Cell]| {
+            let mut hasher = DefaultHasher::new();
+            cells.hash(&mut hasher);
+            hasher.finish()
+        };
+        let rows: Vec<u64> = (top..=bottom)
+            .map(|row| hash_row(&self.cells[row * width..(row + 1) * width]))
+            .collect();
+        let painted_rows: Vec<u64> = (top..=bottom)
+            .map(|row| hash_row(&self.painted_cells[row * width..(row + 1) * width]))
+            .collect();
+        // rows exposed by the scroll are blank
+        let blank = hash_row(&vec![Cell::empty(); width]);
+
+        let mut painted_index: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (index, &hash) in painted_rows.iter().enumerate() {
+            painted_index.entry(hash).or_default().push(index);
+        }
+        let mut try_up = vec![false; height];
+        let mut try_down = vec![false; height];
+        for (index, hash) in rows.iter().enumerate() {
+            for &painted in painted_index.get(hash).into_iter().flatten() {
+                if painted > index {
+                    try_up[painted - index] = true;
+                } else if painted < index {
+                    try_down[index - painted] = true;
+                }
+            }
+        }
+
+        // a mismatch caused by hash collisions only costs bytes, the cells are still compared
+        // one by one after scrolling.
+        let mut best = None;
+        let mut best_matched = matched;
+        for shift in 1..height {
+            if try_up[shift] {
+                let up = (0..height)
+                    .filter(|&index| match painted_rows.get(index + shift) {
+                        Some(&painted) => rows[index] == painted,
+                        None => rows[index] == blank,
+                    })
+                    .count();
+                if up > best_matched {
+                    best = Some(shift as isize);
+                    best_matched = up;
+                }
+            }
+            if try_down[shift] {
+                let down = (0..height)
+                    .filter(|&index| match index.checked_sub(shift) {
+                        Some(painted) => rows[index] == painted_rows[painted],
+                        None => rows[index] == blank,
+                    })
+                    .count();
+                if down > best_matched {
+                    best = Some(-(shift as isize));
+                    best_matched = down;
+                }
+            }
+        }
+
+        // scrolling costs a few bytes, make sure that more than one row is saved
+        if best_matched >= matched + 2 {
+            best.map(|shift| (top, bottom, shift))
+        } else {
+            None
+        }
+    }
+
+    /// shift the painted rows `top..=bottom` as the terminal does when scrolled
+    fn scroll_painted(&mut self, top: usize, bottom: usize, shift: isize) {
+        let width = self.width;
+        let amount = shift.unsigned_abs();
+        let (start, end) = (top * width, (bottom + 1) * width);
+        if shift > 0 {
+            self.painted_cells
+                .copy_within(start + amount * width..end, start);
+            for cell in self.painted_cells[end - amount * width..end].iter_mut() {
+                *cell = Cell::empty();
+            }
+        } else {
+            self.painted_cells
+                .copy_within(start..end - amount * width, start + amount * width);
+            for cell in self.painted_cells[start..start + amount * width].iter_mut() {
+                *cell = Cell::empty();
+            }
+        }
+    }
+
     /// ```
     /// use tuikit::cell::Cell;
     /// use tuikit::canvas::Canvas;
@@ -206,9 +442,9 @@ impl Screen {
     ///
     ///
     /// let mut screen = Screen::new(1, 1);
-    /// screen.put_cell(0, 0, Cell{ ch: 'a', ..Cell::default()});
+    /// screen.put_cell(0, 0, Cell::from('a'));
     /// let mut iter = screen.iter_cell();
-    /// assert_eq!(Some((0, 0, &Cell{ ch: 'a', ..Cell::default()})), iter.next());
+    /// assert_eq!(Some((0, 0, &Cell::from('a'))), iter.next());
     /// assert_eq!(None, iter.next());
     /// ```
     pub fn iter_cell(&self) -> CellIterator {
@@ -218,6 +454,24 @@ impl Screen {
             vec: &self.cells,
         };
     }
+
+    /// get the cell of position `(row, col)` as it was painted by the last `present`
+    ///
+    /// ```
+    /// use tuikit::cell::Cell;
+    /// use tuikit::canvas::Canvas;
+    /// use tuikit::screen::Screen;
+    ///
+    /// let mut screen = Screen::new(2, 1);
+    /// screen.put_cell(0, 0, Cell::from('a'));
+    /// assert_eq!(Cell::default(), screen.painted_cell(0, 0).unwrap());
+    ///
+    /// screen.present();
+    /// assert_eq!(Cell::from('a'), screen.painted_cell(0, 0).unwrap());
+    /// ```
+    pub fn painted_cell(&self, row: usize, col: usize) -> Result<Cell> {
+        self.index(row, col).map(|index| self.painted_cells[index])
+    }
 }
 
 impl Canvas for Screen {
@@ -236,11 +490,11 @@ impl Canvas for Screen {
 
     /// change a cell of position `(row, col)` to `cell`
     fn put_cell(&mut self, row: usize, col: usize, cell: Cell) -> Result<usize> {
-        let ch_width = cell.ch.width().unwrap_or(2);
+        let ch_width = cell.ch.width();
         if ch_width > 1 {
             let _ = self.index(row, col + 1).map(|index| {
                 self.cells[index - 1] = cell;
-                self.cells[index].ch = ' ';
+                self.cells[index].ch = ' '.into();
             });
         } else {
             let _ = self.index(row, col).map(|index| {
@@ -263,6 +517,33 @@ impl Canvas for Screen {
         self.cursor.visible = show;
         Ok(())
     }
+
+    /// change the shape and color of the cursor
+    fn set_cursor_style(&mut self, style: CursorStyle) -> Result<()> {
+        self.cursor.style = style;
+        Ok(())
+    }
+}
+
+/// the number of decimal digits of `x`
+fn digits(x: usize) -> usize {
+    x.to_string().len()
+}
+
+/// move the cursor from `from` to `(row, col)`, relative moves are used when they are shorter
+/// than the absolute one(`CSI row ; col H`).
+fn cursor_move(from: Cursor, row: usize, col: usize) -> Command {
+    let absolute_len = 4 + digits(row + 1) + digits(col + 1);
+    if row == from.row && col > from.col {
+        let amount = col - from.col;
+        let relative_len = if amount == 1 { 3 } else { 3 + digits(amount) };
+        if relative_len < absolute_len {
+            return Command::CursorRight(amount);
+        }
+    } else if row == from.row + 1 && col == 0 {
+        return Command::CursorNextLine;
+    }
+    Command::CursorGoto { row, col }
 }
 
 pub struct CellIterator<'a> {
@@ -291,6 +572,7 @@ struct Cursor {
     pub row: usize,
     pub col: usize,
     visible: bool,
+    style: CursorStyle,
 }
 
 impl Default for Cursor {
@@ -299,6 +581,7 @@ impl Default for Cursor {
             row: 0,
             col: 0,
             visible: false,
+            style: CursorStyle::default(),
         }
     }
 }
@@ -310,88 +593,399 @@ mod test {
     #[test]
     fn test_cell_iterator() {
         let mut screen = Screen::new(2, 2);
-        let _ = screen.put_cell(
-            0,
-            0,
-            Cell {
-                ch: 'a',
-                attr: Attr::default(),
-            },
-        );
-        let _ = screen.put_cell(
-            0,
-            1,
-            Cell {
-                ch: 'b',
-                attr: Attr::default(),
-            },
-        );
-        let _ = screen.put_cell(
-            1,
-            0,
-            Cell {
-                ch: 'c',
-                attr: Attr::default(),
-            },
-        );
-        let _ = screen.put_cell(
-            1,
-            1,
-            Cell {
-                ch: 'd',
-                attr: Attr::default(),
-            },
-        );
+        let _ = screen.put_cell(0, 0, Cell::from('a'));
+        let _ = screen.put_cell(0, 1, Cell::from('b'));
+        let _ = screen.put_cell(1, 0, Cell::from('c'));
+        let _ = screen.put_cell(1, 1, Cell::from('d'));
 
         let mut iter = screen.iter_cell();
+        assert_eq!(Some((0, 0, &Cell::from('a'))), iter.next());
+        assert_eq!(Some((0, 1, &Cell::from('b'))), iter.next());
+        assert_eq!(Some((1, 0, &Cell::from('c'))), iter.next());
+        assert_eq!(Some((1, 1, &Cell::from('d'))), iter.next());
+        assert_eq!(None, iter.next());
+
+        let empty_screen = Screen::new(0, 0);
+        let mut empty_iter = empty_screen.iter_cell();
+        assert_eq!(None, empty_iter.next());
+    }
+
+    #[test]
+    fn test_present_grapheme() {
+        let mut screen = Screen::new(6, 1);
+        assert_eq!(1, screen.print(0, 0, "e\u{301}").unwrap());
+        assert_eq!(2, screen.print(0, 1, "👩‍🔬").unwrap());
+        assert_eq!(1, screen.print(0, 3, "x").unwrap());
+
+        let commands = screen.present();
+        let written: Vec<_> = commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::PutChar(ch) => Some(ch.to_string()),
+                Command::Write(content) => Some(content.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec!["e\u{301}", "👩‍🔬", "x"], written);
+        assert_eq!("👩‍🔬", screen.painted_cell(0, 1).unwrap().ch.as_str());
+        assert_eq!(' ', screen.painted_cell(0, 2).unwrap().ch);
+    }
+
+    #[test]
+    fn test_present_grapheme_with_control() {
+        let mut screen = Screen::new(3, 1);
+        let cell = Cell::default().grapheme("\r\n");
+        screen.put_cell(0, 0, cell).unwrap();
+        screen.print(0, 1, "x").unwrap();
+
+        let commands = screen.present();
+        let written: Vec<_> = commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::PutChar(ch) => Some(ch.to_string()),
+                Command::Write(content) => Some(content.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![" ", "x"], written);
+    }
+
+    #[test]
+    fn test_present_underline_color() {
+        use crate::attr::{Color, Effect};
+
+        let curly = Attr::default().effect(Effect::CURLY_UNDERLINE);
+        let mut screen = Screen::new(2, 1);
+        let _ = screen.print_with_attr(0, 0, "ab", curly);
+        let _ = screen.present();
+
+        // only the underline color changes, the cell should still be repainted
+        let red = curly.underline_color(Color::Rgb(255, 0, 0));
+        let _ = screen.print_with_attr(0, 0, "a", red);
+        let commands = screen.present();
+        let attrs: Vec<_> = commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::ChangeAttribute { to, .. } => Some(*to),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![red], attrs);
+    }
+
+    #[test]
+    fn test_present_hyperlink() {
+        use crate::cell::Hyperlink;
+
+        let link = Hyperlink::new("https://example.com").unwrap();
+        let mut screen = Screen::new(8, 2);
+        let _ = screen.print(0, 0, "a");
+        let _ = screen.print_link(0, 1, "bc", link, Attr::default());
+        let _ = screen.print(0, 3, "d");
+        let _ = screen.print_link(1, 0, "e", link, Attr::default());
+
+        let commands = screen.present();
+        let sequence: Vec<_> = commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::PutChar(ch) => Some(ch.to_string()),
+                Command::Hyperlink(Some(link)) => Some(format!("<{}>", link.uri())),
+                Command::Hyperlink(None) => Some("</>".to_string()),
+                _ => None,
+            })
+            .collect();
         assert_eq!(
-            Some((
-                0,
-                0,
-                &Cell {
-                    ch: 'a',
-                    attr: Attr::default()
-                }
-            )),
-            iter.next()
+            vec![
+                "a",
+                "<https://example.com>",
+                "b",
+                "c",
+                "</>",
+                "d",
+                "<https://example.com>",
+                "e",
+                "</>"
+            ],
+            sequence
         );
+
+        // unlinking a cell repaints it
+        let _ = screen.print(1, 0, "e");
+        let commands = screen.present();
+        assert!(commands.iter().all(|command| match command {
+            Command::Hyperlink(_) => false,
+            _ => true,
+        }));
+        assert_eq!(None, screen.painted_cell(1, 0).unwrap().link);
+    }
+
+    #[test]
+    fn test_present_cursor_style() {
+        use crate::output::CursorShape;
+
+        let styles = |commands: Vec<Command>| -> Vec<CursorStyle> {
+            commands
+                .into_iter()
+                .filter_map(|command| match command {
+                    Command::CursorStyle(style) => Some(style),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let mut screen = Screen::new(2, 1);
+        assert!(styles(screen.present()).is_empty());
+
+        let bar = CursorStyle::new(CursorShape::Bar);
+        let _ = screen.set_cursor_style(bar);
+        assert_eq!(vec![bar], styles(screen.present()));
+        assert!(styles(screen.present()).is_empty());
+        assert_eq!(bar, screen.painted_cursor_style());
+
+        // the style is painted again after the terminal is restored
+        screen.invalidate();
+        assert_eq!(vec![bar], styles(screen.present()));
+    }
+
+    #[test]
+    fn test_present_scroll() {
+        let written = |commands: &[Command]| -> String {
+            commands
+                .iter()
+                .filter_map(|command| match command {
+                    Command::PutChar(ch) => Some(*ch),
+                    _ => None,
+                })
+                .collect()
+        };
+        let draw = |screen: &mut Screen, items: &[&str]| {
+            let _ = screen.clear();
+            for (row, item) in items.iter().enumerate() {
+                let _ = screen.print(row, 0, item);
+            }
+        };
+
+        let items = ["a0", "b1", "c2", "d3", "e4", "f5"];
+        let mut screen = Screen::new(4, 5);
+        draw(&mut screen, &items[..5]);
+        let _ = screen.present();
+
+        // scrolled down the list by a line, only the last line is painted
+        draw(&mut screen, &items[1..6]);
+        let commands = screen.present();
+        assert!(matches!(
+            commands[3],
+            Command::SetScrollRegion { top: 0, bottom: 4 }
+        ));
+        assert!(matches!(commands[4], Command::ScrollUp(1)));
+        assert_eq!("f5", written(&commands));
+        for row in 0..5 {
+            assert!(screen.row_matches(row, row));
+        }
+
+        // and back, the first line is painted
+        draw(&mut screen, &items[..5]);
+        let commands = screen.present();
+        assert!(matches!(commands[4], Command::ScrollDown(1)));
+        assert_eq!("a0", written(&commands));
+
+        // the header is fixed, only the rows below it are scrolled
+        let mut screen = Screen::new(4, 5);
+        draw(&mut screen, &["##", "a0", "b1", "c2", "d3"]);
+        let _ = screen.present();
+        draw(&mut screen, &["##", "c2", "d3", "e4", "f5"]);
+        let commands = screen.present();
+        assert!(matches!(
+            commands[3],
+            Command::SetScrollRegion { top: 1, bottom: 4 }
+        ));
+        assert!(matches!(commands[4], Command::ScrollUp(2)));
+        assert_eq!("e4f5", written(&commands));
+
+        // not worth scrolling if only a line is changed
+        draw(&mut screen, &["##", "c2", "xx", "e4", "f5"]);
+        let commands = screen.present();
+        assert!(!commands
+            .iter()
+            .any(|command| matches!(command, Command::SetScrollRegion { .. })));
+        assert_eq!("xx", written(&commands));
+    }
+
+    #[test]
+    fn test_present_relative_moves() {
+        let moves = |commands: Vec<Command>| -> Vec<String> {
+            commands
+                .into_iter()
+                .skip(3) // hide cursor, go home, reset attributes
+                .filter_map(|command| match command {
+                    Command::CursorGoto { row, col } => Some(format!("goto {},{}", row, col)),
+                    Command::CursorRight(amount) => Some(format!("right {}", amount)),
+                    Command::CursorNextLine => Some("next line".to_string()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let mut screen = Screen::new(20, 3);
+        let _ = screen.print(0, 0, "ab");
+        let _ = screen.print(0, 4, "c");
+        let _ = screen.print(1, 0, "d");
+        let _ = screen.print(2, 15, "e");
+        let _ = screen.present();
+
+        let _ = screen.print(0, 0, "AB");
+        let _ = screen.print(0, 4, "C");
+        let _ = screen.print(1, 0, "D");
+        let _ = screen.print(2, 15, "E");
         assert_eq!(
-            Some((
-                0,
-                1,
-                &Cell {
-                    ch: 'b',
-                    attr: Attr::default()
-                }
-            )),
-            iter.next()
+            vec![
+                "right 2",   // skip the unchanged cells
+                "next line", // to the beginning of the next line
+                "goto 2,15", // relative moves are not used across lines
+                "goto 0,0",  // restore the cursor
+            ],
+            moves(screen.present())
         );
-        assert_eq!(
-            Some((
-                1,
-                0,
-                &Cell {
-                    ch: 'c',
-                    attr: Attr::default()
+    }
+
+    #[test]
+    fn test_present_erase_once() {
+        let erased = |commands: Vec<Command>| {
+            commands
+                .iter()
+                .filter(|command| matches!(command, Command::EraseEndOfLine))
+                .count()
+        };
+
+        let mut screen = Screen::new(4, 2);
+        screen.clear_on_start(true);
+        let _ = screen.clear();
+        let _ = screen.print(0, 0, "ab");
+        assert_eq!(2, erased(screen.present()));
+
+        // the trailing blanks are not erased again
+        let _ = screen.print(0, 0, "cd");
+        assert_eq!(0, erased(screen.present()));
+
+        let _ = screen.clear();
+        let _ = screen.print(0, 0, "c");
+        assert_eq!(1, erased(screen.present()));
+    }
+
+    #[test]
+    fn test_present_erase_after_invalidate() {
+        let erased = |commands: Vec<Command>| {
+            commands
+                .iter()
+                .filter(|command| matches!(command, Command::EraseEndOfLine))
+                .count()
+        };
+
+        let mut screen = Screen::new(4, 3);
+        screen.clear_on_start(true);
+        let _ = screen.clear();
+        let _ = screen.print(0, 0, "ab");
+        let _ = screen.print(1, 0, "abcd");
+        let _ = screen.present();
+        assert_eq!(0, erased(screen.present()));
+
+        // the terminal may hold anything after it is restored, the rows are painted again
+        screen.invalidate();
+        let commands = screen.present();
+        assert!(commands
+            .iter()
+            .any(|command| matches!(command, Command::PutChar('d'))));
+        assert_eq!(2, erased(commands));
+        assert_eq!(0, erased(screen.present()));
+    }
+
+    #[test]
+    fn test_present_erase_shrunk_row() {
+        let mut screen = Screen::new(6, 1);
+        screen.clear_on_start(true);
+        let _ = screen.print(0, 0, "abcd");
+        let _ = screen.present();
+
+        // the cells no longer used are erased
+        let _ = screen.clear();
+        let _ = screen.print(0, 0, "ab");
+        let commands = screen.present();
+        let erased_from: Vec<_> = commands
+            .windows(3)
+            .filter_map(|commands| match commands {
+                [Command::CursorGoto { row, col }, Command::ResetAttributes, Command::EraseEndOfLine] => {
+                    Some((*row, *col))
                 }
-            )),
-            iter.next()
-        );
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![(0, 2)], erased_from);
+
+        // nothing is written for an unchanged frame
+        let _ = screen.clear();
+        let _ = screen.print(0, 0, "ab");
+        let commands = screen.present();
+        assert!(!commands.iter().any(|command| matches!(
+            command,
+            Command::EraseEndOfLine | Command::PutChar(_) | Command::Write(_)
+        )));
+    }
+
+    #[test]
+    fn test_present_runs() {
+        use crate::attr::Color;
+
+        let blue = Attr::default().bg(Color::BLUE);
+        let draw = |screen: &mut Screen| {
+            let _ = screen.print(0, 0, &"─".repeat(30));
+            let _ = screen.print(0, 30, "ab");
+            let _ = screen.print_with_attr(1, 0, &" ".repeat(30), blue);
+            let _ = screen.print(1, 30, "cd");
+            let _ = screen.print(2, 0, "xxx");
+        };
+
+        // disabled by default
+        let mut screen = Screen::new(32, 3);
+        draw(&mut screen);
+        assert!(!screen
+            .present()
+            .iter()
+            .any(|command| matches!(command, Command::RepeatChar(..) | Command::EraseChars(_))));
+
+        let mut screen = Screen::new(32, 3);
+        screen.use_repeat(true);
+        screen.use_erase_chars(true);
+        draw(&mut screen);
+        let commands = screen.present();
+        let runs: Vec<_> = commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::RepeatChar(..) | Command::EraseChars(_) => Some(format!("{:?}", command)),
+                Command::PutChar(ch) => Some(ch.to_string()),
+                Command::CursorRight(amount) => Some(format!("right {}", amount)),
+                _ => None,
+            })
+            .collect();
         assert_eq!(
-            Some((
-                1,
-                1,
-                &Cell {
-                    ch: 'd',
-                    attr: Attr::default()
-                }
-            )),
-            iter.next()
+            vec![
+                "RepeatChar('─', 30)",
+                "a",
+                "b",
+                "EraseChars(30)",
+                "right 30", // erasing doesn't move the cursor
+                "c",
+                "d",
+                "x", // too short to be worth it
+                "x",
+                "x",
+            ],
+            runs
         );
-        assert_eq!(None, iter.next());
-
-        let empty_screen = Screen::new(0, 0);
-        let mut empty_iter = empty_screen.iter_cell();
-        assert_eq!(None, empty_iter.next());
+        assert_eq!(blue, screen.painted_cell(1, 29).unwrap().attr);
+        assert_eq!('─', screen.painted_cell(0, 29).unwrap().ch);
+        assert!(screen
+            .present()
+            .iter()
+            .all(|command| !matches!(command, Command::PutChar(_))));
     }
 }
@@ -1,22 +1,37 @@
 use lazy_static::lazy_static;
-use nix::sys::signal::{pthread_sigmask, sigaction};
+use nix::sys::signal::{pthread_sigmask, raise, sigaction};
 use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, SigmaskHow, Signal};
+use nix::sys::termios::{tcsetattr, SetArg, Termios};
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::convert::TryFrom;
+use std::os::unix::io::RawFd;
+use std::panic;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::Mutex;
 use std::sync::Once;
-use std::thread;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
 
 lazy_static! {
     static ref NOTIFIER_COUNTER: AtomicUsize = AtomicUsize::new(1);
     static ref NOTIFIER: Mutex<HashMap<usize, Sender<()>>> = Mutex::new(HashMap::new());
+    static ref JOB_CONTROL_NOTIFIER: Mutex<HashMap<usize, Sender<Signal>>> =
+        Mutex::new(HashMap::new());
+    static ref RESTORERS: Mutex<HashMap<usize, Restorer>> = Mutex::new(HashMap::new());
 }
 
 static ONCE: Once = Once::new();
+static RESTORE_HOOKS_ONCE: Once = Once::new();
+static EXIT_SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+static JOB_CONTROL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+// wait up to 1 second for the restorers to be unlocked
+const RESTORE_LOCK_RETRIES: usize = 100;
+const RESTORE_LOCK_INTERVAL: Duration = Duration::from_millis(10);
 
 pub fn initialize_signals() {
-    ONCE.call_once(listen_sigwinch);
+    ONCE.call_once(listen_signals);
 }
 
 pub fn notify_on_sigwinch() -> (usize, Receiver<()>) {
@@ -32,9 +47,28 @@ pub fn unregister_sigwinch(id: usize) -> Option<Sender<()>> {
     notifiers.remove(&id)
 }
 
+/// get notified on job control signals, i.e. SIGTSTP & SIGCONT
+pub fn notify_on_job_control() -> (usize, Receiver<Signal>) {
+    let (tx, rx) = channel();
+    let new_id = NOTIFIER_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut notifiers = JOB_CONTROL_NOTIFIER.lock().unwrap();
+    notifiers.entry(new_id).or_insert(tx);
+    (new_id, rx)
+}
+
+pub fn unregister_job_control(id: usize) -> Option<Sender<Signal>> {
+    let mut notifiers = JOB_CONTROL_NOTIFIER.lock().unwrap();
+    notifiers.remove(&id)
+}
+
 extern "C" fn handle_sigwiwnch(_: i32) {}
 
-fn listen_sigwinch() {
+extern "C" fn handle_job_control_signal(signal: i32) {
+    // only async-signal-safe functions are allowed here, leave the work to the listener thread
+    let _ = nix::unistd::write(JOB_CONTROL_PIPE.load(Ordering::SeqCst), &[signal as u8]);
+}
+
+fn listen_signals() {
     let (tx_sig, rx_sig) = channel();
 
     // register terminal resize event, `pthread_sigmask` should be run before any thread.
@@ -53,19 +87,237 @@ fn listen_sigwinch() {
         let _ = sigaction(Signal::SIGWINCH, &action);
     }
 
+    let tx_resize = tx_sig.clone();
     thread::spawn(move || {
         // listen to the resize event;
         loop {
-            let _errno = sigset.wait();
-            let _ = tx_sig.send(());
+            if let Ok(signal) = sigset.wait() {
+                let _ = tx_resize.send(signal);
+            }
         }
     });
 
+    listen_job_control_signals(tx_sig);
+
     thread::spawn(move || {
-        while let Ok(_) = rx_sig.recv() {
-            let notifiers = NOTIFIER.lock().unwrap();
-            for (_, sender) in notifiers.iter() {
-                let _ = sender.send(());
+        while let Ok(signal) = rx_sig.recv() {
+            if signal == Signal::SIGWINCH {
+                let notifiers = NOTIFIER.lock().unwrap();
+                for (_, sender) in notifiers.iter() {
+                    let _ = sender.send(());
+                }
+            } else {
+                let notifiers = JOB_CONTROL_NOTIFIER.lock().unwrap();
+                if signal == Signal::SIGTSTP && notifiers.is_empty() {
+                    // no one handles it (e.g. all terms are dropped), stop as SIGTSTP normally does
+                    drop(notifiers);
+                    let _ = stop_process();
+                    continue;
+                }
+                for (_, sender) in notifiers.iter() {
+                    let _ = sender.send(signal);
+                }
+            }
+        }
+    });
+}
+
+/// Catch SIGTSTP & SIGCONT with handlers that forward them through a pipe, so that they are
+/// received no matter which thread they are delivered to. Note that SIGTSTP will no longer stop
+/// the process, it is up to the notified ones, see `stop_process`.
+fn listen_job_control_signals(tx_sig: Sender<Signal>) {
+    let (rx, tx) = match nix::unistd::pipe() {
+        Ok(pipe) => pipe,
+        Err(_) => return,
+    };
+    JOB_CONTROL_PIPE.store(tx, Ordering::SeqCst);
+
+    // restart the interrupted system calls, the process keeps running after the signals
+    let action = SigAction::new(
+        SigHandler::Handler(handle_job_control_signal),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+
+    for &signal in [Signal::SIGTSTP, Signal::SIGCONT].iter() {
+        if let Ok(previous) = unsafe { sigaction(signal, &action) } {
+            if previous.handler() == SigHandler::SigIgn {
+                // e.g. SIGTSTP in shells without job control, keep it ignored
+                let _ = unsafe { sigaction(signal, &previous) };
+            }
+        }
+    }
+
+    thread::spawn(move || {
+        let mut buf = [0; 1];
+        while let Ok(1) = nix::unistd::read(rx, &mut buf) {
+            if let Ok(signal) = Signal::try_from(buf[0] as i32) {
+                let _ = tx_sig.send(signal);
+            }
+        }
+    });
+}
+
+/// Stop the process as SIGTSTP normally does, returns after the process is continued.
+///
+/// SIGTSTP is raised with its default action and unblocked meanwhile, so that the shell reports
+/// the job as stopped by SIGTSTP and the parent is notified, the handler and the signal mask are
+/// restored afterwards.
+pub fn stop_process() -> nix::Result<()> {
+    let default = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+    let previous = unsafe { sigaction(Signal::SIGTSTP, &default)? };
+
+    let mut sigset = SigSet::empty();
+    sigset.add(Signal::SIGTSTP);
+    let mut previous_mask = SigSet::empty();
+    let result = pthread_sigmask(
+        SigmaskHow::SIG_UNBLOCK,
+        Some(&sigset),
+        Some(&mut previous_mask),
+    )
+    .and_then(|_| raise(Signal::SIGTSTP));
+
+    let _ = pthread_sigmask(SigmaskHow::SIG_SETMASK, Some(&previous_mask), None);
+    let _ = unsafe { sigaction(Signal::SIGTSTP, &previous) };
+    result
+}
+
+/// The things to do for restoring a terminal on abnormal exits
+pub struct Restorer {
+    pub fd: RawFd,
+    pub sequence: Vec<u8>,
+    pub termios: Option<Termios>,
+    /// the thread that owns the terminal, whose panic brings the terminal down
+    pub owner: ThreadId,
+}
+
+pub fn register_restorer(restorer: Restorer) -> usize {
+    let new_id = NOTIFIER_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut restorers = RESTORERS.lock().unwrap_or_else(|err| err.into_inner());
+    restorers.insert(new_id, restorer);
+    new_id
+}
+
+/// replace the sequence of a registered restorer, e.g. after the state of the terminal changed
+pub fn update_restorer(id: usize, sequence: Vec<u8>) {
+    let mut restorers = RESTORERS.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(restorer) = restorers.get_mut(&id) {
+        restorer.sequence = sequence;
+    }
+}
+
+/// return `None` if the terminal had been restored by `restore_terminals`
+pub fn unregister_restorer(id: usize) -> Option<Restorer> {
+    let mut restorers = RESTORERS.lock().unwrap_or_else(|err| err.into_inner());
+    restorers.remove(&id)
+}
+
+/// restore all the registered terminals, they are unregistered afterwards
+pub fn restore_terminals() {
+    restore_terminals_if(|_| true);
+}
+
+/// Restore the terminals if the panic is going to bring the process down, i.e. it is on the main
+/// thread or on the thread that owns the terminal. Panics on other threads might be caught or
+/// only end the thread, the app keeps running with its terminals.
+fn restore_terminals_on_panic() {
+    let current = thread::current();
+    if current.name() == Some("main") {
+        restore_terminals();
+    } else {
+        restore_terminals_if(|restorer| restorer.owner == current.id());
+    }
+}
+
+fn restore_terminals_if(predicate: impl Fn(&Restorer) -> bool) {
+    let mut restorers = match lock_restorers() {
+        Some(restorers) => restorers,
+        None => return,
+    };
+
+    let ids: Vec<usize> = restorers
+        .iter()
+        .filter(|(_, restorer)| predicate(restorer))
+        .map(|(&id, _)| id)
+        .collect();
+    for id in ids {
+        let restorer = restorers.remove(&id).unwrap();
+        let _ = nix::unistd::write(restorer.fd, &restorer.sequence);
+        if let Some(termios) = restorer.termios {
+            let _ = tcsetattr(restorer.fd, SetArg::TCSANOW, &termios);
+        }
+    }
+}
+
+/// The lock is only held for a moment by the others, e.g. `update_restorer` on every frame, so
+/// wait for it. But it might never be released if held by the panicking thread itself, thus the
+/// waiting is bounded.
+fn lock_restorers() -> Option<MutexGuard<'static, HashMap<usize, Restorer>>> {
+    for _ in 0..RESTORE_LOCK_RETRIES {
+        match RESTORERS.try_lock() {
+            Ok(restorers) => return Some(restorers),
+            Err(TryLockError::Poisoned(err)) => return Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) => thread::sleep(RESTORE_LOCK_INTERVAL),
+        }
+    }
+    None
+}
+
+/// Restore the terminals before the panic message is printed if the panic brings the process
+/// down, and on SIGTERM, SIGINT & SIGHUP before the process is terminated.
+pub fn install_restore_hooks() {
+    RESTORE_HOOKS_ONCE.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore_terminals_on_panic();
+            default_hook(info);
+        }));
+
+        listen_exit_signals();
+    });
+}
+
+extern "C" fn handle_exit_signal(signal: i32) {
+    // only async-signal-safe functions are allowed here, leave the work to the listener thread
+    let _ = nix::unistd::write(EXIT_SIGNAL_PIPE.load(Ordering::SeqCst), &[signal as u8]);
+}
+
+fn listen_exit_signals() {
+    let (rx, tx) = match nix::unistd::pipe() {
+        Ok(pipe) => pipe,
+        Err(_) => return,
+    };
+    EXIT_SIGNAL_PIPE.store(tx, Ordering::SeqCst);
+
+    let action = SigAction::new(
+        SigHandler::Handler(handle_exit_signal),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+
+    let mut previous_actions = HashMap::new();
+    for &signal in [Signal::SIGTERM, Signal::SIGINT, Signal::SIGHUP].iter() {
+        if let Ok(previous) = unsafe { sigaction(signal, &action) } {
+            if previous.handler() == SigHandler::SigIgn {
+                // e.g. SIGHUP under `nohup`, keep it ignored
+                let _ = unsafe { sigaction(signal, &previous) };
+            } else {
+                previous_actions.insert(signal, previous);
+            }
+        }
+    }
+
+    thread::spawn(move || {
+        let mut buf = [0; 1];
+        while let Ok(1) = nix::unistd::read(rx, &mut buf) {
+            restore_terminals();
+
+            // hand the signal over to the previous handler, normally it terminates the process
+            if let Ok(signal) = Signal::try_from(buf[0] as i32) {
+                if let Some(previous) = previous_actions.get(&signal) {
+                    let _ = unsafe { sigaction(signal, previous) };
+                }
+                let _ = raise(signal);
             }
         }
     });
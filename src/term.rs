@@ -23,13 +23,18 @@
 //! terminals as a table of fixed-size cells and input being a stream of structured messages
 
 use std::cmp::{max, min};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
 use std::sync::Arc;
-use std::thread;
-use std::time::Duration;
+#[cfg(feature = "event-stream")]
+use std::task::Waker;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
 
-use crate::attr::Attr;
+use crate::attr::{Attr, Color, ColorDepth};
 use crate::canvas::Canvas;
 use crate::cell::Cell;
 use crate::draw::Draw;
@@ -38,16 +43,31 @@ use crate::event::Event;
 use crate::input::{KeyBoard, KeyboardHandler};
 use crate::key::Key;
 use crate::output::Command;
-use crate::output::Output;
+use crate::output::{
+    ClipboardSelection, CursorStyle, KeyboardEnhancementFlags, MouseTracking, Output,
+};
 use crate::raw::{get_tty, IntoRawMode};
 use crate::screen::Screen;
 use crate::spinlock::SpinLock;
 use crate::sys::signal::{initialize_signals, notify_on_sigwinch, unregister_sigwinch};
+use crate::sys::signal::{notify_on_job_control, stop_process, unregister_job_control};
+use crate::sys::signal::{register_restorer, unregister_restorer, update_restorer, Restorer};
 use crate::Result;
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::sys::signal::Signal;
+use nix::sys::termios::Termios;
 
 const MIN_HEIGHT: usize = 1;
 const WAIT_TIMEOUT: Duration = Duration::from_millis(300);
 const POLLING_TIMEOUT: Duration = Duration::from_millis(10);
+const SYNCHRONIZED_UPDATE_MODE: u16 = 2026;
+const BRACKETED_PASTE_MODE: u16 = 2004;
+const FOCUS_EVENT_MODE: u16 = 1004;
+const DEFAULT_MAX_FPS: u32 = 60;
+// the components started by `restart`: the key listener & the size change listener
+const COMPONENTS: usize = 2;
+// the attribute of primary device attributes(DA1) that indicates sixel graphics
+const SIXEL_ATTRIBUTE: u64 = 1 << 4;
 
 #[derive(Debug, Copy, Clone)]
 pub enum TermHeight {
@@ -55,14 +75,102 @@ pub enum TermHeight {
     Percent(usize),
 }
 
+/// Install a panic hook and handlers of SIGTERM, SIGINT & SIGHUP, so that the terminal is
+/// restored (i.e. show cursor, quit alternate screen, disable mouse, restore termios) even if
+/// the `Term` is not dropped on abnormal exits.
+///
+/// The panic message will be printed after the restoration, the previously installed panic hook
+/// and signal handlers are called afterwards.
+///
+/// Only panics that bring the process down are handled, i.e. the ones on the main thread, or on
+/// the thread that created the `Term`. Panics on other threads might be caught (e.g. by thread
+/// pools) or only end the thread, the terminals are left alone for the app keeps running.
+///
+/// ```no_run
+/// use tuikit::prelude::*;
+/// use tuikit::term::install_restore_hooks;
+///
+/// install_restore_hooks();
+/// let term: Term<()> = Term::new().unwrap();
+/// panic!("the terminal is restored before printing this");
+/// ```
+pub fn install_restore_hooks() {
+    crate::sys::signal::install_restore_hooks();
+}
+
+/// Whether to wrap each frame in a synchronized update (DEC mode 2026), so that terminals
+/// supporting it won't show a half-drawn frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SynchronizedOutput {
+    Disabled,
+    /// Always wrap the frames, terminals that don't support it will ignore the sequences
+    Enabled,
+    /// Query the terminal with DECRQM on (re)start, and wrap the frames only if it is supported
+    Detect,
+}
+
+/// whether the key is a reply to queries rather than a key stroke
+fn is_reply(key: &Key) -> bool {
+    matches!(
+        key,
+        Key::CursorPos(..)
+            | Key::ModeReport(..)
+            | Key::ColorReport(..)
+            | Key::DeviceAttributes(..)
+            | Key::KittyKeyboardReport(..)
+            | Key::TerminalVersion(..)
+    )
+}
+
+/// Features supported by the terminal, see `Term::detect_capabilities`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TerminalCapabilities {
+    /// The name and version of the terminal reported by XTVERSION, e.g. "kitty(0.26.5)"
+    pub version: Option<String>,
+    /// The class of the terminal reported by DA1, e.g. 62 for VT220, 0 if not reported
+    pub device_class: u16,
+    /// The attributes(< 64) reported by DA1, in ascending order
+    pub device_attributes: Vec<u16>,
+    /// Synchronized updates (DEC mode 2026)
+    pub synchronized_output: bool,
+    /// Bracketed paste (DEC mode 2004)
+    pub bracketed_paste: bool,
+    /// Focus events (DEC mode 1004)
+    pub focus_events: bool,
+    /// The kitty keyboard protocol
+    pub kitty_keyboard: bool,
+    /// Sixel graphics
+    pub sixel: bool,
+    /// 24 bit colors, detected from the environment as terminals have no way to report it
+    pub true_color: bool,
+}
+
 pub struct Term<UserEvent: Send + 'static = ()> {
     components_to_stop: Arc<AtomicUsize>,
     keyboard_handler: SpinLock<Option<KeyboardHandler>>,
     resize_signal_id: Arc<AtomicUsize>,
-    term_lock: SpinLock<TermLock>,
+    term_lock: Arc<SpinLock<TermLock>>,
     event_rx: SpinLock<Receiver<Event<UserEvent>>>,
-    event_tx: Arc<SpinLock<Sender<Event<UserEvent>>>>,
+    event_tx: Arc<EventSender<UserEvent>>,
     raw_mouse: bool, // to produce raw mouse event or the parsed event(e.g. DoubleClick)
+    bracketed_paste: bool, // to collect pasted contents into `Event::Paste`
+    headless: Option<(usize, usize)>,
+    headless_input: SpinLock<Option<File>>, // write end of the headless keyboard's input
+    job_control_id: usize,
+    job_control: Arc<JobControl<UserEvent>>,
+    replies: Arc<SpinLock<Option<Sender<Key>>>>, // receives the replies of a pending query
+    redraw_tx: SpinLock<Sender<()>>, // wakes the render scheduler up, see `request_redraw`
+    timer_tx: SpinLock<Sender<TimerRequest>>, // adds/cancels the timers, see `add_timer`
+}
+
+/// requests to the timer thread
+enum TimerRequest {
+    Add {
+        id: usize,
+        delay: Duration,
+        repeat: bool,
+    },
+    Cancel(usize),
 }
 
 pub struct TermOptions {
@@ -72,9 +180,17 @@ pub struct TermOptions {
     clear_on_exit: bool,
     clear_on_start: bool,
     mouse_enabled: bool,
+    mouse_tracking: MouseTracking,
     raw_mouse: bool,
+    bracketed_paste: bool,
+    focus_events: bool,
     hold: bool, // to start term or not on creation
     disable_alternate_screen: bool,
+    kitty_keyboard: KeyboardEnhancementFlags,
+    synchronized_output: SynchronizedOutput,
+    color_depth: Option<ColorDepth>,
+    max_fps: u32,
+    headless: Option<(usize, usize)>,
 }
 
 impl Default for TermOptions {
@@ -86,9 +202,17 @@ impl Default for TermOptions {
             clear_on_exit: true,
             clear_on_start: true,
             mouse_enabled: false,
+            mouse_tracking: MouseTracking::Click,
             raw_mouse: false,
+            bracketed_paste: false,
+            focus_events: false,
             hold: false,
             disable_alternate_screen: false,
+            kitty_keyboard: KeyboardEnhancementFlags::empty(),
+            synchronized_output: SynchronizedOutput::Disabled,
+            color_depth: None,
+            max_fps: DEFAULT_MAX_FPS,
+            headless: None,
         }
     }
 }
@@ -120,6 +244,24 @@ impl TermOptions {
         self.mouse_enabled = enabled;
         self
     }
+    /// Which mouse events to report once mouse is enabled, `MouseTracking::Click` by default.
+    /// Use `Drag` to receive `Key::MouseDrag`, and `Any` to also receive `Key::MouseMove`.
+    pub fn mouse_tracking(mut self, tracking: MouseTracking) -> Self {
+        self.mouse_tracking = tracking;
+        self
+    }
+    /// Enable bracketed paste, pasted contents will be delivered as a single `Event::Paste`
+    /// instead of key strokes. Disabled by default.
+    pub fn bracketed_paste(mut self, enabled: bool) -> Self {
+        self.bracketed_paste = enabled;
+        self
+    }
+    /// Report `Event::FocusGained`/`Event::FocusLost` when the terminal window gains/loses focus.
+    /// Disabled by default.
+    pub fn focus_events(mut self, enabled: bool) -> Self {
+        self.focus_events = enabled;
+        self
+    }
     pub fn raw_mouse(mut self, enabled: bool) -> Self {
         self.raw_mouse = enabled;
         self
@@ -132,6 +274,40 @@ impl TermOptions {
         self.disable_alternate_screen = disable_alternate_screen;
         self
     }
+    /// Enable the kitty keyboard protocol with `flags`. Disabled(empty) by default.
+    ///
+    /// Keys that could not be represented by the legacy `Key` variants (e.g. `Shift-Enter`, key
+    /// release events) will be reported as `Key::Extended`.
+    pub fn kitty_keyboard(mut self, flags: KeyboardEnhancementFlags) -> Self {
+        self.kitty_keyboard = flags;
+        self
+    }
+    /// Wrap each frame of `present` in a synchronized update to avoid tearing. Disabled by
+    /// default.
+    pub fn synchronized_output(mut self, synchronized_output: SynchronizedOutput) -> Self {
+        self.synchronized_output = synchronized_output;
+        self
+    }
+    /// Force the color depth instead of detecting it from `NO_COLOR`, `COLORTERM` and terminfo.
+    /// Colors beyond the depth are mapped to the nearest one.
+    pub fn color_depth(mut self, color_depth: ColorDepth) -> Self {
+        self.color_depth = Some(color_depth);
+        self
+    }
+    /// The maximal number of frames presented per second for `Term::request_redraw`, 60 by
+    /// default. `Term::present` is not limited.
+    pub fn max_fps(mut self, max_fps: u32) -> Self {
+        self.max_fps = max_fps;
+        self
+    }
+    /// Run the term without a real terminal, with fixed size `(width, height)`.
+    ///
+    /// Contents are rendered to memory, key strokes could be fed with `Term::feed_input`.
+    /// Useful for testing applications.
+    pub fn headless(mut self, width: usize, height: usize) -> Self {
+        self.headless = Some((width, height));
+        self
+    }
 }
 
 impl<UserEvent: Send + 'static> Term<UserEvent> {
@@ -176,15 +352,39 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
 
         let (event_tx, event_rx) = channel();
         let raw_mouse = options.raw_mouse;
+        let (job_control_id, job_control_rx) = notify_on_job_control();
+        let (redraw_tx, redraw_rx) = channel();
+        let (timer_tx, timer_rx) = channel();
+        let term_lock = Arc::new(SpinLock::new(TermLock::with_options(&options)));
+        let event_tx = Arc::new(EventSender::new(event_tx)?);
+        let replies = Arc::new(SpinLock::new(None));
+        let job_control = Arc::new(JobControl {
+            term_lock: term_lock.clone(),
+            event_tx: event_tx.clone(),
+            replies: replies.clone(),
+            headless: options.headless,
+            suspended: AtomicBool::new(false),
+        });
         let ret = Term {
             components_to_stop: Arc::new(AtomicUsize::new(0)),
             keyboard_handler: SpinLock::new(None),
             resize_signal_id: Arc::new(AtomicUsize::new(0)),
-            term_lock: SpinLock::new(TermLock::with_options(&options)),
-            event_tx: Arc::new(SpinLock::new(event_tx)),
+            term_lock,
+            event_tx,
             event_rx: SpinLock::new(event_rx),
             raw_mouse,
+            bracketed_paste: options.bracketed_paste,
+            headless: options.headless,
+            headless_input: SpinLock::new(None),
+            job_control_id,
+            job_control,
+            replies,
+            redraw_tx: SpinLock::new(redraw_tx),
+            timer_tx: SpinLock::new(timer_tx),
         };
+        ret.start_job_control_listener(job_control_rx);
+        ret.start_render_scheduler(redraw_rx, options.max_fps);
+        ret.start_timer(timer_rx);
         if options.hold {
             Ok(ret)
         } else {
@@ -192,8 +392,13 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
         }
     }
 
+    /// all the components are started, i.e. the term is neither paused nor being paused
+    fn is_running(&self) -> bool {
+        self.components_to_stop.load(Ordering::SeqCst) == COMPONENTS
+    }
+
     fn ensure_not_stopped(&self) -> Result<()> {
-        if self.components_to_stop.load(Ordering::SeqCst) == 2 {
+        if self.is_running() {
             Ok(())
         } else {
             Err(TuikitError::TerminalNotStarted)
@@ -216,28 +421,225 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
         Ok((0, 0))
     }
 
+    /// Query if the terminal recognizes the DEC private `mode` with DECRQM.
+    ///
+    /// A cursor position request is sent after the query, terminals answer in order, so the
+    /// cursor report arriving alone means the query is not supported.
+    fn query_mode(&self, keyboard: &mut KeyBoard, output: &mut Output, mode: u16) -> bool {
+        output.request_mode(mode);
+        output.ask_for_cpr();
+
+        let mut recognized = false;
+        while let Ok(key) = keyboard.next_key_timeout(WAIT_TIMEOUT) {
+            match key {
+                Key::ModeReport(m, setting) if m == mode => {
+                    recognized = setting != 0 && setting != 4;
+                }
+                Key::CursorPos(..) => break,
+                _ => {}
+            }
+        }
+        recognized
+    }
+
+    /// Send requests to the running terminal and collect the replies (mode reports, color
+    /// reports, ...) instead of emitting them as events.
+    ///
+    /// Like `query_mode`, a cursor position request is sent after the requests to mark the end
+    /// of the replies, so that terminals that don't answer won't cost the whole timeout.
+    fn query_terminal(&self, request: impl FnOnce(&mut Output)) -> Result<Vec<Key>> {
+        self.ensure_not_stopped()?;
+        let (reply_tx, reply_rx) = channel();
+        self.replies.lock().replace(reply_tx);
+
+        {
+            let mut termlock = self.term_lock.lock();
+            match termlock.output.as_mut() {
+                Some(output) => {
+                    request(output);
+                    output.ask_for_cpr();
+                }
+                None => {
+                    self.replies.lock().take();
+                    return Err(TuikitError::TerminalNotStarted);
+                }
+            }
+        }
+
+        let mut replies = Vec::new();
+        while let Ok(key) = reply_rx.recv_timeout(WAIT_TIMEOUT) {
+            match key {
+                Key::CursorPos(..) => break,
+                key => replies.push(key),
+            }
+        }
+        self.replies.lock().take();
+        Ok(replies)
+    }
+
+    /// Query the default foreground and background colors of the terminal (OSC 10/11), e.g. to
+    /// decide whether the terminal is dark or light.
+    ///
+    /// return `(foreground, background)` as `Color::Rgb`, a color is `Color::Default` if the
+    /// terminal doesn't answer in time.
+    ///
+    /// ```no_run
+    /// use tuikit::prelude::*;
+    ///
+    /// let term: Term<()> = Term::new().unwrap();
+    /// if let Ok((_, Color::Rgb(r, g, b))) = term.query_default_colors() {
+    ///     let is_dark = (r as u32 + g as u32 + b as u32) < 384;
+    /// }
+    /// ```
+    pub fn query_default_colors(&self) -> Result<(Color, Color)> {
+        let replies = self.query_terminal(|output| output.request_default_colors())?;
+        let mut colors = (Color::Default, Color::Default);
+        for reply in replies {
+            match reply {
+                Key::ColorReport(10, _, color) => colors.0 = color,
+                Key::ColorReport(11, _, color) => colors.1 = color,
+                _ => {}
+            }
+        }
+        Ok(colors)
+    }
+
+    /// Query the colors of the palette entries `indices` (OSC 4).
+    ///
+    /// return the colors in the order of `indices`, a color is `Color::Default` if the terminal
+    /// doesn't answer in time.
+    pub fn query_palette_colors(&self, indices: &[u8]) -> Result<Vec<Color>> {
+        let replies = self.query_terminal(|output| output.request_palette_colors(indices))?;
+        let mut colors = vec![Color::Default; indices.len()];
+        for reply in replies {
+            if let Key::ColorReport(4, index, color) = reply {
+                for (i, _) in indices.iter().enumerate().filter(|&(_, &x)| x == index) {
+                    colors[i] = color;
+                }
+            }
+        }
+        Ok(colors)
+    }
+
+    /// Detect the features supported by the terminal with primary device attributes(DA1),
+    /// XTVERSION, DECRQM and the kitty keyboard protocol queries. It is best called at startup.
+    ///
+    /// The result is kept, so that it could be consulted by `capabilities` later. If
+    /// `SynchronizedOutput::Detect` is specified, synchronized updates are also enabled
+    /// accordingly.
+    ///
+    /// ```no_run
+    /// use tuikit::prelude::*;
+    ///
+    /// let term: Term<()> = Term::new().unwrap();
+    /// let capabilities = term.detect_capabilities().unwrap();
+    /// if capabilities.sixel {
+    ///     // draw images
+    /// }
+    /// ```
+    pub fn detect_capabilities(&self) -> Result<TerminalCapabilities> {
+        let modes = [
+            SYNCHRONIZED_UPDATE_MODE,
+            BRACKETED_PASTE_MODE,
+            FOCUS_EVENT_MODE,
+        ];
+        let replies = self.query_terminal(|output| {
+            output.request_terminal_version();
+            for &mode in modes.iter() {
+                output.request_mode(mode);
+            }
+            output.request_kitty_keyboard_flags();
+            output.request_device_attributes();
+        })?;
+
+        let mut capabilities = TerminalCapabilities::default();
+        for reply in replies {
+            match reply {
+                Key::TerminalVersion(version) => capabilities.version = Some(version.to_string()),
+                Key::ModeReport(mode, setting) => {
+                    let recognized = setting != 0 && setting != 4;
+                    match mode {
+                        SYNCHRONIZED_UPDATE_MODE => capabilities.synchronized_output = recognized,
+                        BRACKETED_PASTE_MODE => capabilities.bracketed_paste = recognized,
+                        FOCUS_EVENT_MODE => capabilities.focus_events = recognized,
+                        _ => {}
+                    }
+                }
+                Key::KittyKeyboardReport(_) => capabilities.kitty_keyboard = true,
+                Key::DeviceAttributes(class, attributes) => {
+                    capabilities.device_class = class;
+                    capabilities.device_attributes = (0..64)
+                        .filter(|&attribute| attributes & (1 << attribute) != 0)
+                        .collect();
+                    capabilities.sixel = attributes & SIXEL_ATTRIBUTE != 0;
+                }
+                _ => {}
+            }
+        }
+
+        let mut termlock = self.term_lock.lock();
+        capabilities.true_color = termlock
+            .output
+            .as_ref()
+            .map(|output| output.color_depth() == ColorDepth::TrueColor)
+            .unwrap_or(false);
+        if termlock.synchronized_output == SynchronizedOutput::Detect {
+            termlock.synchronized_update = capabilities.synchronized_output;
+        }
+        termlock.capabilities = Some(capabilities.clone());
+        Ok(capabilities)
+    }
+
+    /// The capabilities found by the last `detect_capabilities`, `None` if not detected yet.
+    pub fn capabilities(&self) -> Option<TerminalCapabilities> {
+        self.term_lock.lock().capabilities.clone()
+    }
+
     /// restart the terminal if it had been stopped
     pub fn restart(&self) -> Result<()> {
         let mut termlock = self.term_lock.lock();
-        if self.components_to_stop.load(Ordering::SeqCst) == 2 {
+        if self.is_running() {
             return Ok(());
         }
 
-        let ttyout = get_tty()?.into_raw_mode()?;
-        let mut output = Output::new(Box::new(ttyout))?;
-        let mut keyboard = KeyBoard::new_with_tty().raw_mouse(self.raw_mouse);
+        let (output, keyboard, cursor_pos, termios) = match self.headless {
+            Some((width, height)) => {
+                // keys are fed through a pipe instead of the tty
+                let (rx, tx) = nix::unistd::pipe()?;
+                self.headless_input
+                    .lock()
+                    .replace(unsafe { File::from_raw_fd(tx) });
+                let output = Output::headless(Box::new(io::sink()), width, height);
+                let keyboard = KeyBoard::new(Box::new(unsafe { File::from_raw_fd(rx) }));
+                (output, keyboard.raw_mouse(self.raw_mouse), (0, 0), None)
+            }
+            None => {
+                let ttyout = get_tty()?.into_raw_mode()?;
+                let termios = ttyout.prev_ios().clone();
+                let mut output = Output::new(Box::new(ttyout))?;
+                let mut keyboard = KeyBoard::new_with_tty().raw_mouse(self.raw_mouse);
+                if termlock.synchronized_output == SynchronizedOutput::Detect {
+                    termlock.synchronized_update =
+                        self.query_mode(&mut keyboard, &mut output, SYNCHRONIZED_UPDATE_MODE);
+                }
+                let cursor_pos = self.get_cursor_pos(&mut keyboard, &mut output)?;
+                (output, keyboard, cursor_pos, Some(termios))
+            }
+        };
         self.keyboard_handler
             .lock()
             .replace(keyboard.get_interrupt_handler());
-        let cursor_pos = self.get_cursor_pos(&mut keyboard, &mut output)?;
         termlock.restart(output, cursor_pos)?;
+        if let Some(termios) = termios {
+            termlock.register_restorer(termios)?;
+        }
 
         // start two listener
         self.start_key_listener(keyboard);
         self.start_size_change_listener();
 
         // wait for components to start
-        while self.components_to_stop.load(Ordering::SeqCst) < 2 {
+        while self.components_to_stop.load(Ordering::SeqCst) < COMPONENTS {
             debug!(
                 "restart: components: {}",
                 self.components_to_stop.load(Ordering::SeqCst)
@@ -245,8 +647,7 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
             thread::sleep(POLLING_TIMEOUT);
         }
 
-        let event_tx = self.event_tx.lock();
-        let _ = event_tx.send(Event::Restarted);
+        let _ = self.event_tx.send(Event::Restarted);
 
         Ok(())
     }
@@ -273,7 +674,10 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
         self.keyboard_handler.lock().take().map(|h| h.interrupt());
         unregister_sigwinch(self.resize_signal_id.load(Ordering::Relaxed)).map(|tx| tx.send(()));
 
-        termlock.pause(exiting)?;
+        // the terminal might had been given back by the job control, e.g. the process is stopped
+        if termlock.output.is_some() {
+            termlock.pause(exiting)?;
+        }
 
         // wait for the components to stop
         while self.components_to_stop.load(Ordering::SeqCst) > 0 {
@@ -284,12 +688,110 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
             thread::sleep(POLLING_TIMEOUT);
         }
 
+        // close the input only after the key listener stops, it should never see EOF
+        self.headless_input.lock().take();
+
         Ok(())
     }
 
+    /// Suspend the process like `Ctrl-Z` does in a shell: pause the Term (thus restore the
+    /// terminal), and stop the process. Once the process is continued (i.e. received `SIGCONT`,
+    /// e.g. by `fg`), the Term will be restarted and an `Event::Restarted` will be emitted.
+    ///
+    /// The same happens automatically when `SIGTSTP` is received.
+    pub fn suspend(&self) -> Result<()> {
+        self.job_control.suspend()
+    }
+
+    /// handle the job control signals right away, the user might not be polling events
+    fn start_job_control_listener(&self, signal_rx: Receiver<Signal>) {
+        let job_control = self.job_control.clone();
+        thread::spawn(move || {
+            debug!("job control listener start");
+            while let Ok(signal) = signal_rx.recv() {
+                let _ = if signal == Signal::SIGTSTP {
+                    job_control.suspend()
+                } else {
+                    job_control.resume()
+                };
+            }
+            debug!("job control listener stop");
+        });
+    }
+
+    /// present the requested redraws, at most `max_fps` frames per second
+    fn start_render_scheduler(&self, redraw_rx: Receiver<()>, max_fps: u32) {
+        let term_lock = self.term_lock.clone();
+        let frame_interval = Duration::from_secs(1) / max(max_fps, 1);
+        thread::spawn(move || {
+            debug!("render scheduler start");
+            // stops once the term is dropped
+            while redraw_rx.recv().is_ok() {
+                // wait for the next frame, the requests in between are coalesced
+                let last_present = term_lock.lock().last_present;
+                if let Some(elapsed) = last_present.map(|instant| instant.elapsed()) {
+                    if elapsed < frame_interval {
+                        thread::sleep(frame_interval - elapsed);
+                    }
+                }
+                while redraw_rx.try_recv().is_ok() {}
+
+                // the redraw might had been fulfilled by `present`
+                let mut termlock = term_lock.lock();
+                if termlock.redraw_requested && termlock.output.is_some() {
+                    let _ = termlock.present();
+                }
+            }
+            debug!("render scheduler stop");
+        });
+    }
+
+    /// emit `Event::Tick` for the timers
+    fn start_timer(&self, timer_rx: Receiver<TimerRequest>) {
+        let event_tx_clone = self.event_tx.clone();
+        thread::spawn(move || {
+            debug!("timer start");
+            // (deadline, id, interval of repeating timers)
+            let mut timers: Vec<(Instant, usize, Option<Duration>)> = Vec::new();
+            loop {
+                let request = match timers.iter().map(|timer| timer.0).min() {
+                    Some(deadline) => {
+                        timer_rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    }
+                    None => timer_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+
+                match request {
+                    Ok(TimerRequest::Add { id, delay, repeat }) => {
+                        // avoid busy loops of zero intervals
+                        let delay = max(delay, Duration::from_millis(1));
+                        timers.retain(|timer| timer.1 != id);
+                        timers.push((Instant::now() + delay, id, Some(delay).filter(|_| repeat)));
+                    }
+                    Ok(TimerRequest::Cancel(id)) => timers.retain(|timer| timer.1 != id),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // the term is dropped
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+
+                let now = Instant::now();
+                for timer in timers.iter_mut().filter(|timer| timer.0 <= now) {
+                    let _ = event_tx_clone.send(Event::Tick(timer.1));
+                    if let Some(interval) = timer.2 {
+                        timer.0 = next_tick(timer.0, interval, now);
+                    }
+                }
+                timers.retain(|timer| timer.2.is_some() || timer.0 > now);
+            }
+            debug!("timer stop");
+        });
+    }
+
     fn start_key_listener(&self, mut keyboard: KeyBoard) {
         let event_tx_clone = self.event_tx.clone();
         let components_to_stop = self.components_to_stop.clone();
+        let replies = self.replies.clone();
+        let bracketed_paste = self.bracketed_paste;
         thread::spawn(move || {
             components_to_stop.fetch_add(1, Ordering::SeqCst);
             debug!("key listener start");
@@ -297,9 +799,33 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
                 let next_key = keyboard.next_key();
                 trace!("next key: {:?}", next_key);
                 match next_key {
+                    Ok(Key::BracketedPasteStart) if bracketed_paste => {
+                        match keyboard.read_bracketed_paste(WAIT_TIMEOUT) {
+                            Ok(content) => {
+                                let _ = event_tx_clone.send(Event::Paste(content));
+                            }
+                            Err(TuikitError::Interrupted) => break,
+                            _ => {} // ignored
+                        }
+                    }
+                    Ok(Key::FocusIn) => {
+                        let _ = event_tx_clone.send(Event::FocusGained);
+                    }
+                    Ok(Key::FocusOut) => {
+                        let _ = event_tx_clone.send(Event::FocusLost);
+                    }
+                    Ok(Key::ClipboardReport(selection)) => {
+                        if let Some(content) = keyboard.take_clipboard() {
+                            let _ = event_tx_clone.send(Event::Clipboard(selection, content));
+                        }
+                    }
+                    Ok(key) if is_reply(&key) && replies.lock().is_some() => {
+                        if let Some(reply_tx) = replies.lock().as_ref() {
+                            let _ = reply_tx.send(key);
+                        }
+                    }
                     Ok(key) => {
-                        let event_tx = event_tx_clone.lock();
-                        let _ = event_tx.send(Event::Key(key));
+                        let _ = event_tx_clone.send(Event::Key(key));
                     }
                     Err(TuikitError::Interrupted) => break,
                     _ => {} // ignored
@@ -323,8 +849,7 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
             debug!("size change listener started");
             loop {
                 if let Ok(_) = sigwinch_rx.recv() {
-                    let event_tx = event_tx_clone.lock();
-                    let _ = event_tx.send(Event::Resize {
+                    let _ = event_tx_clone.send(Event::Resize {
                         width: 0,
                         height: 0,
                     });
@@ -337,8 +862,9 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
         });
     }
 
-    fn filter_event(&self, event: Event<UserEvent>) -> Event<UserEvent> {
-        match event {
+    /// adjust the events for the user, `None` if nothing is left, e.g. mouse events out of the term
+    fn filter_event(&self, event: Event<UserEvent>) -> Option<Event<UserEvent>> {
+        let event = match event {
             Event::Resize { .. } => {
                 {
                     let mut termlock = self.term_lock.lock();
@@ -347,93 +873,115 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
                 let (width, height) = self.term_size().unwrap_or((0, 0));
                 Event::Resize { width, height }
             }
-            Event::Key(Key::MousePress(button, row, col)) => {
-                // adjust mouse event position
-                let cursor_row = self.term_lock.lock().get_term_start_row() as u16;
-                if row < cursor_row {
-                    Event::__Nonexhaustive
-                } else {
-                    Event::Key(Key::MousePress(button, row - cursor_row, col))
-                }
-            }
-            Event::Key(Key::MouseRelease(row, col)) => {
-                // adjust mouse event position
-                let cursor_row = self.term_lock.lock().get_term_start_row() as u16;
-                if row < cursor_row {
-                    Event::__Nonexhaustive
-                } else {
-                    Event::Key(Key::MouseRelease(row - cursor_row, col))
-                }
-            }
-            Event::Key(Key::MouseHold(row, col)) => {
+            Event::Key(key) => {
                 // adjust mouse event position
-                let cursor_row = self.term_lock.lock().get_term_start_row() as u16;
-                if row < cursor_row {
-                    Event::__Nonexhaustive
-                } else {
-                    Event::Key(Key::MouseHold(row - cursor_row, col))
-                }
-            }
-            Event::Key(Key::SingleClick(button, row, col)) => {
-                let cursor_row = self.term_lock.lock().get_term_start_row() as u16;
-                if row < cursor_row {
-                    Event::__Nonexhaustive
-                } else {
-                    Event::Key(Key::SingleClick(button, row - cursor_row, col))
-                }
-            }
-            Event::Key(Key::DoubleClick(button, row, col)) => {
-                let cursor_row = self.term_lock.lock().get_term_start_row() as u16;
-                if row < cursor_row {
-                    Event::__Nonexhaustive
-                } else {
-                    Event::Key(Key::DoubleClick(button, row - cursor_row, col))
-                }
-            }
-            Event::Key(Key::WheelUp(row, col, num)) => {
-                let cursor_row = self.term_lock.lock().get_term_start_row() as u16;
-                if row < cursor_row {
-                    Event::__Nonexhaustive
-                } else {
-                    Event::Key(Key::WheelUp(row - cursor_row, col, num))
-                }
-            }
-            Event::Key(Key::WheelDown(row, col, num)) => {
-                let cursor_row = self.term_lock.lock().get_term_start_row() as u16;
-                if row < cursor_row {
-                    Event::__Nonexhaustive
-                } else {
-                    Event::Key(Key::WheelDown(row - cursor_row, col, num))
-                }
+                let key = key.map_mouse_position(|row, col| {
+                    let cursor_row = self.term_lock.lock().get_term_start_row() as u16;
+                    row.checked_sub(cursor_row).map(|row| (row, col))
+                })?;
+                Event::Key(key)
             }
             ev => ev,
-        }
+        };
+        Some(event)
+    }
+
+    /// Dequeue a pending event, if there is none, drain the pipe behind `event_fd` before
+    /// waiting for new ones, the events sent after the drain will make the fd readable again.
+    fn try_recv_or_drain(&self, event_rx: &Receiver<Event<UserEvent>>) -> Option<Event<UserEvent>> {
+        event_rx.try_recv().ok().or_else(|| {
+            self.event_tx.drain_pending();
+            None
+        })
     }
 
     /// Wait an event up to `timeout` and return it
     pub fn peek_event(&self, timeout: Duration) -> Result<Event<UserEvent>> {
         let event_rx = self.event_rx.lock();
-        event_rx
-            .recv_timeout(timeout)
-            .map(|ev| self.filter_event(ev))
-            .map_err(|_| TuikitError::Timeout(timeout))
+        let deadline = Instant::now() + timeout;
+        loop {
+            let event = match self.try_recv_or_drain(&event_rx) {
+                Some(event) => event,
+                None => event_rx
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .map_err(|_| TuikitError::Timeout(timeout))?,
+            };
+            if let Some(event) = self.filter_event(event) {
+                return Ok(event);
+            }
+        }
     }
 
     /// Wait for an event indefinitely and return it
     pub fn poll_event(&self) -> Result<Event<UserEvent>> {
         let event_rx = self.event_rx.lock();
-        event_rx
-            .recv()
-            .map(|ev| self.filter_event(ev))
-            .map_err(|err| TuikitError::ChannelReceiveError(err))
+        loop {
+            let event = match self.try_recv_or_drain(&event_rx) {
+                Some(event) => event,
+                None => event_rx
+                    .recv()
+                    .map_err(|err| TuikitError::ChannelReceiveError(err))?,
+            };
+            if let Some(event) = self.filter_event(event) {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Return an event if there is any pending, without blocking
+    ///
+    /// Together with `event_fd`, events could be polled by an event loop such as mio/epoll.
+    pub fn try_event(&self) -> Option<Event<UserEvent>> {
+        let event_rx = self.event_rx.lock();
+        loop {
+            let event = match self.try_recv_or_drain(&event_rx) {
+                Some(event) => event,
+                None => event_rx.try_recv().ok()?,
+            };
+            if let Some(event) = self.filter_event(event) {
+                return Some(event);
+            }
+        }
+    }
+
+    /// A file descriptor that becomes readable when there are events pending.
+    ///
+    /// It is level-triggered: once readable, the events should be consumed with `try_event`
+    /// until it returns `None` (or `peek_event` times out), after that the fd will not be
+    /// readable until new events arrive.
+    /// Note that the readiness could be spurious. Do NOT read from the fd directly.
+    ///
+    /// ```
+    /// use nix::poll::{poll, PollFd, PollFlags};
+    /// use tuikit::prelude::*;
+    ///
+    /// let term: Term<()> = Term::with_options(TermOptions::default().headless(80, 24)).unwrap();
+    /// term.send_event(Event::User(())).unwrap();
+    ///
+    /// let mut fds = [PollFd::new(term.event_fd(), PollFlags::POLLIN)];
+    /// assert_eq!(1, poll(&mut fds, 1000).unwrap());
+    /// assert_eq!(Some(Event::Restarted), term.try_event());
+    /// assert_eq!(Some(Event::User(())), term.try_event());
+    /// assert_eq!(None, term.try_event());
+    /// assert_eq!(0, poll(&mut fds, 0).unwrap());
+    /// ```
+    pub fn event_fd(&self) -> RawFd {
+        self.event_tx.pending_rx.as_raw_fd()
+    }
+
+    /// Return the events as a `futures_core::Stream`, so that they could be `select!`ed with
+    /// other futures in async applications. Requires the `event-stream` feature.
+    ///
+    /// Events are shared by the stream and `poll_event`/`peek_event`/`try_event`, avoid calling
+    /// the blocking ones while the stream is being polled.
+    #[cfg(feature = "event-stream")]
+    pub fn event_stream(&self) -> EventStream<'_, UserEvent> {
+        EventStream { term: self }
     }
 
     /// An interface to inject event to the terminal's event queue
     pub fn send_event(&self, event: Event<UserEvent>) -> Result<()> {
-        let event_tx = self.event_tx.lock();
-        event_tx
-            .send(event)
-            .map_err(|err| TuikitError::SendEventError(err.to_string()))
+        self.event_tx.send(event)
     }
 
     /// Sync internal buffer with terminal
@@ -443,6 +991,125 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
         termlock.present()
     }
 
+    /// Emit `Event::Tick(id)` every `interval`, e.g. to animate a spinner, till it is cancelled
+    /// with `cancel_timer`. A timer or timeout with the same `id` is replaced.
+    ///
+    /// Ticks are sent to the event queue, so they wake up `peek_event`/`poll_event` like
+    /// any other events do.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use tuikit::prelude::*;
+    ///
+    /// const SPINNER: usize = 1;
+    /// let term: Term<()> = Term::new().unwrap();
+    /// term.add_timer(Duration::from_millis(100), SPINNER).unwrap();
+    /// while let Ok(ev) = term.poll_event() {
+    ///     match ev {
+    ///         Event::Tick(SPINNER) => { /* draw the next frame of the spinner */ }
+    ///         Event::Key(Key::ESC) => break,
+    ///         _ => {}
+    ///     }
+    /// }
+    /// ```
+    pub fn add_timer(&self, interval: Duration, id: usize) -> Result<()> {
+        self.send_timer_request(TimerRequest::Add {
+            id,
+            delay: interval,
+            repeat: true,
+        })
+    }
+
+    /// Emit `Event::Tick(id)` once after `delay`. A timer or timeout with the same `id` is
+    /// replaced.
+    pub fn add_timeout(&self, delay: Duration, id: usize) -> Result<()> {
+        self.send_timer_request(TimerRequest::Add {
+            id,
+            delay,
+            repeat: false,
+        })
+    }
+
+    /// Cancel the timer or timeout `id`. Note that ticks already in the event queue are not
+    /// withdrawn.
+    pub fn cancel_timer(&self, id: usize) -> Result<()> {
+        self.send_timer_request(TimerRequest::Cancel(id))
+    }
+
+    fn send_timer_request(&self, request: TimerRequest) -> Result<()> {
+        self.timer_tx
+            .lock()
+            .send(request)
+            .map_err(|err| TuikitError::SendEventError(err.to_string()))
+    }
+
+    /// Request to present the content in the background, like `present` but at most
+    /// `TermOptions::max_fps` frames are presented per second. Requests made within a frame are
+    /// coalesced, so it could be called for every update, e.g. every new line of a log.
+    ///
+    /// `present` is still the way to present immediately, pending requests are fulfilled by it.
+    ///
+    /// ```no_run
+    /// use tuikit::prelude::*;
+    ///
+    /// let term: Term<()> = Term::with_options(TermOptions::default().max_fps(30)).unwrap();
+    /// for i in 0..1000 {
+    ///     let _ = term.print(0, 0, &format!("progress: {}", i));
+    ///     let _ = term.request_redraw();
+    /// }
+    /// ```
+    pub fn request_redraw(&self) -> Result<()> {
+        self.ensure_not_stopped()?;
+        self.term_lock.lock().redraw_requested = true;
+        let _ = self.redraw_tx.lock().send(());
+        Ok(())
+    }
+
+    /// Feed raw bytes to a headless term's keyboard, as if they were typed in the terminal.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tuikit::prelude::*;
+    ///
+    /// let term: Term<()> = Term::with_options(TermOptions::default().headless(80, 24)).unwrap();
+    /// let _ = term.peek_event(Duration::from_secs(1)); // Event::Restarted
+    ///
+    /// term.feed_input(b"\x1b[A").unwrap();
+    /// assert_eq!(Event::Key(Key::Up), term.peek_event(Duration::from_secs(1)).unwrap());
+    /// ```
+    pub fn feed_input(&self, bytes: &[u8]) -> Result<()> {
+        self.ensure_not_stopped()?;
+        let mut headless_input = self.headless_input.lock();
+        let input = headless_input
+            .as_mut()
+            .ok_or(TuikitError::TerminalNotStarted)?;
+        input.write_all(bytes)?;
+        Ok(())
+    }
+
+    /// Take the commands generated by `present` since the last call.
+    ///
+    /// Commands are only recorded by headless terms, others will always return empty result.
+    pub fn take_commands(&self) -> Vec<Command> {
+        let mut termlock = self.term_lock.lock();
+        termlock.take_commands()
+    }
+
+    /// Get the cell of position `(row, col)` as it was rendered by the last `present`
+    ///
+    /// ```
+    /// use tuikit::prelude::*;
+    ///
+    /// let term: Term<()> = Term::with_options(TermOptions::default().headless(80, 24)).unwrap();
+    /// term.print(0, 0, "hello").unwrap();
+    /// term.present().unwrap();
+    /// assert_eq!('e', term.rendered_cell(0, 1).unwrap().ch);
+    /// ```
+    pub fn rendered_cell(&self, row: usize, col: usize) -> Result<Cell> {
+        let termlock = self.term_lock.lock();
+        termlock.rendered_cell(row, col)
+    }
+
     /// Return the printable size(width, height) of the term
     pub fn term_size(&self) -> Result<(usize, usize)> {
         self.ensure_not_stopped()?;
@@ -496,6 +1163,14 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
         termlock.show_cursor(show)
     }
 
+    /// Change the shape and color of the cursor, the style configured by the user is restored
+    /// when the terminal is paused or stopped.
+    pub fn set_cursor_style(&self, style: CursorStyle) -> Result<()> {
+        self.ensure_not_stopped()?;
+        let mut termlock = self.term_lock.lock();
+        termlock.set_cursor_style(style)
+    }
+
     /// Enable mouse support
     pub fn enable_mouse_support(&self) -> Result<()> {
         self.ensure_not_stopped()?;
@@ -510,6 +1185,23 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
         termlock.disable_mouse_support()
     }
 
+    /// Copy `text` to the clipboard with OSC 52, which works over SSH as well.
+    ///
+    /// Note that some terminals limit the size of the text or need it to be enabled first.
+    pub fn set_clipboard(&self, selection: ClipboardSelection, text: &str) -> Result<()> {
+        self.ensure_not_stopped()?;
+        let mut termlock = self.term_lock.lock();
+        termlock.set_clipboard(selection, text)
+    }
+
+    /// Ask the terminal for the contents of the clipboard with OSC 52, they are delivered as
+    /// `Event::Clipboard` if the terminal supports and allows it. Nothing happens otherwise.
+    pub fn request_clipboard(&self, selection: ClipboardSelection) -> Result<()> {
+        self.ensure_not_stopped()?;
+        let mut termlock = self.term_lock.lock();
+        termlock.request_clipboard(selection)
+    }
+
     /// Whether to clear the terminal upon exiting. Defaults to true.
     pub fn clear_on_exit(&self, clear: bool) -> Result<()> {
         self.ensure_not_stopped()?;
@@ -518,24 +1210,292 @@ impl<UserEvent: Send + 'static> Term<UserEvent> {
         Ok(())
     }
 
-    pub fn draw(&self, draw: &dyn Draw) -> Result<()> {
-        let mut canvas = TermCanvas { term: &self };
-        draw.draw(&mut canvas)
-            .map_err(|err| TuikitError::DrawError(err))
+    /// Change the preferred height of the term, see `TermOptions::height`.
+    ///
+    /// Lines are reserved/released immediately (fullscreen mode is entered/left if necessary),
+    /// and an `Event::Resize` is emitted so that the contents could be redrawn.
+    ///
+    /// ```no_run
+    /// use tuikit::prelude::*;
+    ///
+    /// let term: Term<()> = Term::with_height(TermHeight::Fixed(3)).unwrap();
+    /// term.set_height(TermHeight::Fixed(10)).unwrap(); // e.g. to show more results
+    /// ```
+    pub fn set_height(&self, height: TermHeight) -> Result<()> {
+        self.update_height(|termlock| termlock.prefer_height = height)
     }
 
-    pub fn draw_mut(&self, draw: &mut dyn Draw) -> Result<()> {
-        let mut canvas = TermCanvas { term: &self };
-        draw.draw_mut(&mut canvas)
-            .map_err(|err| TuikitError::DrawError(err))
+    /// Change the min height of the term, see `set_height`
+    pub fn set_min_height(&self, min_height: TermHeight) -> Result<()> {
+        self.update_height(|termlock| termlock.min_height = min_height)
     }
-}
 
-impl<'a, UserEvent: Send + 'static> Drop for Term<UserEvent> {
-    fn drop(&mut self) {
-        let _ = self.pause_internal(true);
+    /// Change the max height of the term, see `set_height`
+    pub fn set_max_height(&self, max_height: TermHeight) -> Result<()> {
+        self.update_height(|termlock| termlock.max_height = max_height)
     }
-}
+
+    fn update_height(&self, update: impl FnOnce(&mut TermLock)) -> Result<()> {
+        let mut termlock = self.term_lock.lock();
+        update(&mut termlock);
+
+        // a paused term will apply the new heights on restart
+        if self.components_to_stop.load(Ordering::SeqCst) == 0 {
+            return Ok(());
+        }
+        termlock.adjust_height()?;
+        self.event_tx.send(Event::Resize {
+            width: 0,
+            height: 0,
+        })
+    }
+
+    /// Insert `height` lines above the term, the lines are drawn by `draw` and become part of the
+    /// terminal's contents (e.g. the scrollback), i.e. they are not managed by the term anymore.
+    ///
+    /// It is meant for the non-fullscreen mode, e.g. to print logs above a progress bar. In the
+    /// fullscreen mode the lines are scrolled out of the screen right away.
+    ///
+    /// ```no_run
+    /// use tuikit::prelude::*;
+    ///
+    /// struct Log(&'static str);
+    ///
+    /// impl Draw for Log {
+    ///     fn draw(&self, canvas: &mut dyn Canvas) -> DrawResult<()> {
+    ///         canvas.print_with_attr(0, 0, self.0, Color::GREEN.into())?;
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let term: Term<()> = Term::with_height(TermHeight::Fixed(1)).unwrap();
+    /// term.print(0, 0, "downloading...").unwrap();
+    /// term.insert_before(1, &Log("Downloaded foo v0.1.0")).unwrap();
+    /// ```
+    pub fn insert_before(&self, height: usize, draw: &dyn Draw) -> Result<()> {
+        self.ensure_not_stopped()?;
+        let mut termlock = self.term_lock.lock();
+        termlock.insert_before(height, draw)
+    }
+
+    pub fn draw(&self, draw: &dyn Draw) -> Result<()> {
+        let mut canvas = TermCanvas { term: &self };
+        draw.draw(&mut canvas)
+            .map_err(|err| TuikitError::DrawError(err))
+    }
+
+    pub fn draw_mut(&self, draw: &mut dyn Draw) -> Result<()> {
+        let mut canvas = TermCanvas { term: &self };
+        draw.draw_mut(&mut canvas)
+            .map_err(|err| TuikitError::DrawError(err))
+    }
+}
+
+impl<'a, UserEvent: Send + 'static> Drop for Term<UserEvent> {
+    fn drop(&mut self) {
+        unregister_job_control(self.job_control_id);
+        let _ = self.pause_internal(true);
+    }
+}
+
+/// The next deadline of a repeating timer that fired at `deadline`, the ticks missed (e.g. the
+/// process was stopped) are skipped rather than fired in a burst.
+fn next_tick(deadline: Instant, interval: Duration, now: Instant) -> Instant {
+    let next = deadline + interval;
+    if next > now {
+        next
+    } else {
+        now + interval
+    }
+}
+
+/// Gives the terminal back on job control and takes it again once the process is continued.
+///
+/// It works on `TermLock` directly rather than pausing the whole `Term`, so that the job control
+/// listener could do it right away instead of waiting for the user to poll events. The key and
+/// size change listeners keep running, they have nothing to read while the process is stopped.
+struct JobControl<UserEvent: Send + 'static> {
+    term_lock: Arc<SpinLock<TermLock>>,
+    event_tx: Arc<EventSender<UserEvent>>,
+    replies: Arc<SpinLock<Option<Sender<Key>>>>,
+    headless: Option<(usize, usize)>,
+    suspended: AtomicBool, // stopped by `suspend`, the following SIGCONT is handled by it
+}
+
+impl<UserEvent: Send + 'static> JobControl<UserEvent> {
+    /// restore the terminal, stop the process and take the terminal again once continued
+    fn suspend(&self) -> Result<()> {
+        // not started or paused by the user, there is nothing to give back or to take again
+        let running = self.give_back()?;
+
+        // no one is going to continue a headless term, act as if it is continued right away
+        if self.headless.is_none() {
+            self.suspended.store(running, Ordering::SeqCst);
+            // returns after the process is continued
+            stop_process()?;
+        }
+
+        if running {
+            self.take()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The process is continued, either from `suspend` or by someone else's `SIGSTOP`, in the
+    /// latter case the terminal might be messed up meanwhile, so take it again anyway.
+    fn resume(&self) -> Result<()> {
+        if self.suspended.swap(false, Ordering::SeqCst) {
+            // already taken by `suspend`
+            Ok(())
+        } else if self.give_back()? {
+            self.take()
+        } else {
+            // paused by the user, leave it alone
+            Ok(())
+        }
+    }
+
+    /// restore the terminal, return false if it is not running
+    fn give_back(&self) -> Result<bool> {
+        let mut termlock = self.term_lock.lock();
+        if termlock.output.is_none() {
+            return Ok(false);
+        }
+        termlock.pause(false)?;
+        Ok(true)
+    }
+
+    /// set up the terminal again as `Term::restart` does and emit `Event::Restarted`
+    fn take(&self) -> Result<()> {
+        let (output, cursor_pos, termios) = match self.headless {
+            Some((width, height)) => {
+                let output = Output::headless(Box::new(io::sink()), width, height);
+                (output, (0, 0), None)
+            }
+            None => {
+                let ttyout = get_tty()?.into_raw_mode()?;
+                let termios = ttyout.prev_ios().clone();
+                let mut output = Output::new(Box::new(ttyout))?;
+                let cursor_pos = self.query_cursor_pos(&mut output);
+                (output, cursor_pos, Some(termios))
+            }
+        };
+
+        {
+            let mut termlock = self.term_lock.lock();
+            termlock.restart(output, cursor_pos)?;
+            if let Some(termios) = termios {
+                termlock.register_restorer(termios)?;
+            }
+        }
+        self.event_tx.send(Event::Restarted)
+    }
+
+    /// the cursor position is replied to the key listener, which is still running
+    fn query_cursor_pos(&self, output: &mut Output) -> (usize, usize) {
+        let (reply_tx, reply_rx) = channel();
+        self.replies.lock().replace(reply_tx);
+        output.ask_for_cpr();
+
+        let mut cursor_pos = (0, 0);
+        while let Ok(key) = reply_rx.recv_timeout(WAIT_TIMEOUT) {
+            if let Key::CursorPos(row, col) = key {
+                cursor_pos = (row as usize, col as usize);
+                break;
+            }
+        }
+        self.replies.lock().take();
+        cursor_pos
+    }
+}
+
+/// The sending half of the event queue.
+///
+/// Besides the channel, it keeps a pipe that is written for every event sent, so that pending
+/// events could be polled through the read end, and it wakes up the event stream's task.
+struct EventSender<UserEvent: Send + 'static> {
+    tx: SpinLock<Sender<Event<UserEvent>>>,
+    pending_rx: File,
+    pending_tx: File,
+    #[cfg(feature = "event-stream")]
+    waker: SpinLock<Option<Waker>>,
+}
+
+impl<UserEvent: Send + 'static> EventSender<UserEvent> {
+    fn new(tx: Sender<Event<UserEvent>>) -> Result<Self> {
+        let (rx, pipe_tx) = nix::unistd::pipe()?;
+        for &fd in [rx, pipe_tx].iter() {
+            let mut flag = OFlag::from_bits_truncate(fcntl(fd, FcntlArg::F_GETFL)?);
+            flag.insert(OFlag::O_NONBLOCK);
+            fcntl(fd, FcntlArg::F_SETFL(flag))?;
+        }
+
+        Ok(Self {
+            tx: SpinLock::new(tx),
+            pending_rx: unsafe { File::from_raw_fd(rx) },
+            pending_tx: unsafe { File::from_raw_fd(pipe_tx) },
+            #[cfg(feature = "event-stream")]
+            waker: SpinLock::new(None),
+        })
+    }
+
+    fn send(&self, event: Event<UserEvent>) -> Result<()> {
+        self.tx
+            .lock()
+            .send(event)
+            .map_err(|err| TuikitError::SendEventError(err.to_string()))?;
+
+        // the pipe being full is fine, it is readable anyway
+        let _ = (&self.pending_tx).write(&[0]);
+
+        #[cfg(feature = "event-stream")]
+        {
+            if let Some(waker) = self.waker.lock().take() {
+                waker.wake();
+            }
+        }
+        Ok(())
+    }
+
+    fn drain_pending(&self) {
+        let mut buf = [0; 1024];
+        while let Ok(n) = (&self.pending_rx).read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+        }
+    }
+}
+
+/// The events of `Term` as a `futures_core::Stream`, created by `Term::event_stream`.
+#[cfg(feature = "event-stream")]
+pub struct EventStream<'a, UserEvent: Send + 'static> {
+    term: &'a Term<UserEvent>,
+}
+
+#[cfg(feature = "event-stream")]
+impl<'a, UserEvent: Send + 'static> futures_core::Stream for EventStream<'a, UserEvent> {
+    type Item = Event<UserEvent>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        use std::task::Poll;
+
+        if let Some(event) = self.term.try_event() {
+            return Poll::Ready(Some(event));
+        }
+
+        // register before checking again, so that events sent in between are not missed
+        self.term.event_tx.waker.lock().replace(cx.waker().clone());
+        match self.term.try_event() {
+            Some(event) => Poll::Ready(Some(event)),
+            None => Poll::Pending,
+        }
+    }
+}
 
 pub struct TermCanvas<'a, UserEvent: Send + 'static> {
     term: &'a Term<UserEvent>,
@@ -571,6 +1531,10 @@ impl<'a, UserEvent: Send + 'static> Canvas for TermCanvas<'a, UserEvent> {
     fn show_cursor(&mut self, show: bool) -> Result<()> {
         self.term.show_cursor(show)
     }
+
+    fn set_cursor_style(&mut self, style: CursorStyle) -> Result<()> {
+        self.term.set_cursor_style(style)
+    }
 }
 
 struct TermLock {
@@ -582,13 +1546,44 @@ struct TermLock {
     clear_on_exit: bool,
     clear_on_start: bool,
     mouse_enabled: bool,
+    mouse_tracking: MouseTracking,
+    bracketed_paste: bool,
+    focus_events: bool,
+    kitty_keyboard: KeyboardEnhancementFlags,
+    synchronized_output: SynchronizedOutput,
+    // wrap the frames in synchronized updates or not, according to `synchronized_output`
+    synchronized_update: bool,
+    // overrides the color depth detected by output
+    color_depth: Option<ColorDepth>,
+    capabilities: Option<TerminalCapabilities>,
     alternate_screen: bool,
     disable_alternate_screen: bool,
     cursor_row: usize,
+    // the cursor position of the normal screen before entering the alternate screen
+    normal_cursor_pos: (usize, usize),
     screen_height: usize,
     screen_width: usize,
     screen: Screen,
     output: Option<Output>,
+    // commands generated by `present`, only recorded for headless term
+    command_log: Option<Vec<Command>>,
+    restorer_id: Option<usize>,
+    owner: ThreadId, // the thread that created the term, see `install_restore_hooks`
+    // the state the sequence of the registered restorer is built from
+    restorer_state: Option<RestoreState>,
+    // a redraw is requested but not presented yet
+    redraw_requested: bool,
+    last_present: Option<Instant>,
+}
+
+/// see `TermLock::restore_state`
+#[derive(PartialEq)]
+struct RestoreState {
+    cursor_style: CursorStyle,
+    clear_on_exit: bool,
+    cursor_row: usize,
+    height: usize,
+    bottom_intact: bool,
 }
 
 impl Default for TermLock {
@@ -601,13 +1596,28 @@ impl Default for TermLock {
             alternate_screen: false,
             disable_alternate_screen: false,
             cursor_row: 0,
+            normal_cursor_pos: (0, 0),
             screen_height: 0,
             screen_width: 0,
             screen: Screen::new(0, 0),
             output: None,
+            command_log: None,
+            restorer_id: None,
+            owner: thread::current().id(),
+            restorer_state: None,
+            redraw_requested: false,
+            last_present: None,
             clear_on_exit: true,
             clear_on_start: true,
             mouse_enabled: false,
+            mouse_tracking: MouseTracking::Click,
+            bracketed_paste: false,
+            focus_events: false,
+            kitty_keyboard: KeyboardEnhancementFlags::empty(),
+            synchronized_output: SynchronizedOutput::Disabled,
+            synchronized_update: false,
+            color_depth: None,
+            capabilities: None,
         }
     }
 }
@@ -623,33 +1633,121 @@ impl TermLock {
         term.screen.clear_on_start(options.clear_on_start);
         term.disable_alternate_screen = options.disable_alternate_screen;
         term.mouse_enabled = options.mouse_enabled;
+        term.mouse_tracking = options.mouse_tracking;
+        term.bracketed_paste = options.bracketed_paste;
+        term.focus_events = options.focus_events;
+        term.kitty_keyboard = options.kitty_keyboard;
+        term.synchronized_output = options.synchronized_output;
+        term.synchronized_update = options.synchronized_output == SynchronizedOutput::Enabled;
+        term.color_depth = options.color_depth;
+        if options.headless.is_some() {
+            term.command_log = Some(Vec::new());
+        }
         term
     }
 
     /// Present the content to the terminal
     pub fn present(&mut self) -> Result<()> {
-        let output = self
-            .output
-            .as_mut()
+        self.output
+            .as_ref()
             .ok_or(TuikitError::TerminalNotStarted)?;
         let mut commands = self.screen.present();
 
-        let cursor_row = self.cursor_row;
         // add cursor_row to all CursorGoto commands
-        for cmd in commands.iter_mut() {
-            if let Command::CursorGoto { row, col } = *cmd {
-                *cmd = Command::CursorGoto {
-                    row: row + cursor_row,
-                    col,
-                }
+        offset_rows(&mut commands, self.cursor_row);
+
+        if self.synchronized_update {
+            commands.insert(0, Command::SynchronizedUpdate(true));
+            commands.push(Command::SynchronizedUpdate(false));
+        }
+
+        self.redraw_requested = false;
+        self.last_present = Some(Instant::now());
+        self.execute(commands)
+    }
+
+    /// Insert `height` lines drawn by `draw` above the managed area, the area is moved down (the
+    /// terminal is scrolled if there is not enough space) and redrawn.
+    pub fn insert_before(&mut self, height: usize, draw: &dyn Draw) -> Result<()> {
+        self.output
+            .as_ref()
+            .ok_or(TuikitError::TerminalNotStarted)?;
+
+        let width = self.screen.width();
+        let mut lines = Screen::new(width, height);
+        draw.draw(&mut lines).map_err(TuikitError::DrawError)?;
+
+        // the lines take the place of the managed area
+        let mut commands = vec![
+            Command::CursorGoto {
+                row: self.cursor_row,
+                col: 0,
+            },
+            Command::EraseDown,
+        ];
+
+        // write the lines one by one, the terminal will scroll if we are at the bottom
+        let mut row = self.cursor_row;
+        let mut cells = lines.iter_cell().map(|(_, _, cell)| *cell);
+        for _ in 0..height {
+            let mut line = Screen::new(width, 1);
+            for (col, cell) in cells.by_ref().take(width).enumerate() {
+                let _ = line.put_cell(0, col, cell);
             }
+            let mut line_commands = line.present();
+            offset_rows(&mut line_commands, row);
+            commands.append(&mut line_commands);
+            commands.push(Command::ResetAttributes);
+            commands.push(Command::Write("\n".to_string()));
+            row = min(row + 1, self.screen_height.saturating_sub(1));
+        }
+
+        // reserve the lines for the managed area
+        let area_height = self.screen.height();
+        for _ in 1..area_height {
+            commands.push(Command::Write("\n".to_string()));
+        }
+        if self.cursor_row + height + area_height > self.screen_height {
+            self.bottom_intact = true;
+        }
+        self.cursor_row = min(row, self.screen_height.saturating_sub(area_height));
+        self.execute(commands)?;
+
+        self.screen.invalidate();
+        self.present()
+    }
+
+    /// execute the commands, they are recorded for headless terms
+    fn execute(&mut self, commands: Vec<Command>) -> Result<()> {
+        let output = self
+            .output
+            .as_mut()
+            .ok_or(TuikitError::TerminalNotStarted)?;
+
+        if let Some(command_log) = self.command_log.as_mut() {
+            command_log.extend(commands.iter().cloned());
         }
 
         for cmd in commands.into_iter() {
             output.execute(cmd);
         }
         output.flush();
-        Ok(())
+
+        // e.g. the cursor style might be changed
+        self.refresh_restorer()
+    }
+
+    /// take the recorded commands
+    pub fn take_commands(&mut self) -> Vec<Command> {
+        self.command_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// get the cell of position `(row, col)` as it was rendered
+    pub fn rendered_cell(&self, row: usize, col: usize) -> Result<Cell> {
+        self.screen.painted_cell(row, col)
     }
 
     /// Resize the internal buffer to according to new terminal size
@@ -689,6 +1787,62 @@ impl TermLock {
 
         // clear the screen buffer
         self.screen.resize(width, height);
+
+        // the rows of the term might be changed
+        self.refresh_restorer()
+    }
+
+    /// Reserve or release lines according to the (changed) height specs
+    fn adjust_height(&mut self) -> Result<()> {
+        let output = self
+            .output
+            .as_mut()
+            .ok_or(TuikitError::TerminalNotStarted)?;
+        let height = Self::calc_preferred_height(
+            &self.min_height,
+            &self.max_height,
+            &self.prefer_height,
+            self.screen_height,
+        );
+
+        let full_screen = height >= self.screen_height;
+        if full_screen == self.alternate_screen && (full_screen || height == self.screen.height()) {
+            return Ok(());
+        }
+
+        if self.alternate_screen || full_screen {
+            // switching between fullscreen and non-fullscreen mode, start over
+            let cursor_pos = if self.alternate_screen && !self.disable_alternate_screen {
+                output.quit_alternate_screen();
+                self.normal_cursor_pos
+            } else {
+                output.cursor_goto(self.cursor_row, 0);
+                output.erase_down();
+                (self.cursor_row, 0)
+            };
+            self.ensure_height(cursor_pos)?;
+        } else if self.cursor_row + height > self.screen_height {
+            // scroll up to reserve lines
+            output.cursor_goto(self.screen_height - 1, 0);
+            for _ in self.screen_height..(self.cursor_row + height) {
+                output.write("\n");
+            }
+            self.cursor_row = self.screen_height - height;
+            self.bottom_intact = true;
+        } else if height < self.screen.height() {
+            // release lines, keep the top line
+            self.bottom_intact = false;
+        }
+
+        // erase the lines & resize the screen buffer
+        self.on_resize()?;
+        let output = self
+            .output
+            .as_mut()
+            .ok_or(TuikitError::TerminalNotStarted)?;
+        output.cursor_goto(self.cursor_row, 0);
+        output.erase_down();
+        output.flush();
         Ok(())
     }
 
@@ -717,28 +1871,106 @@ impl TermLock {
 
     /// Pause the terminal
     fn pause(&mut self, exiting: bool) -> Result<()> {
-        self.disable_mouse()?;
-        self.output.take().map(|mut output| {
-            output.show_cursor();
-            if self.clear_on_exit || !exiting {
-                // clear drawn contents
-                if !self.disable_alternate_screen {
-                    output.quit_alternate_screen();
-                } else {
-                    output.cursor_goto(self.cursor_row, 0);
-                    output.erase_down();
-                }
+        let mut output = self.output.take().ok_or(TuikitError::TerminalNotStarted)?;
+
+        // the terminal might had been restored on panic, do not mess up the panic messages
+        let restored = matches!(self.restorer_id.take().map(unregister_restorer), Some(None));
+        if !restored {
+            self.write_restore(&mut output, exiting);
+        }
+        output.flush();
+
+        // the cursor style is restored, paint it again on restart
+        self.screen.invalidate();
+        Ok(())
+    }
+
+    /// write the sequences that give the terminal back, e.g. disable mouse, quit alternate screen
+    fn write_restore(&self, output: &mut Output, exiting: bool) {
+        output.disable_mouse_support();
+        if self.bracketed_paste {
+            output.disable_bracketed_paste();
+        }
+        if self.focus_events {
+            output.disable_focus_events();
+        }
+        if !self.kitty_keyboard.is_empty() {
+            output.disable_kitty_keyboard();
+        }
+        output.show_cursor();
+        if self.screen.painted_cursor_style() != CursorStyle::default() {
+            output.set_cursor_style(CursorStyle::default());
+        }
+        if self.clear_on_exit || !exiting {
+            // clear drawn contents
+            if !self.disable_alternate_screen {
+                output.quit_alternate_screen();
             } else {
-                output.cursor_goto(self.cursor_row + self.screen.height(), 0);
-                if self.bottom_intact {
-                    output.write("\n");
-                }
+                output.cursor_goto(self.cursor_row, 0);
+                output.erase_down();
             }
-            output.flush();
-        });
+        } else {
+            output.cursor_goto(self.cursor_row + self.screen.height(), 0);
+            if self.bottom_intact {
+                output.write("\n");
+            }
+        }
+    }
+
+    /// register the sequences to restore the terminal on abnormal exits, see
+    /// `install_restore_hooks`
+    fn register_restorer(&mut self, termios: Termios) -> Result<()> {
+        let (sequence, fd) = self.restore_sequence()?;
+        if let Some(fd) = fd {
+            self.restorer_id = Some(register_restorer(Restorer {
+                fd,
+                sequence,
+                termios: Some(termios),
+                owner: self.owner,
+            }));
+            self.restorer_state = Some(self.restore_state());
+        }
         Ok(())
     }
 
+    /// rebuild the sequences of the registered restorer if the state they depend on is changed
+    /// after the term is started, e.g. the rows of the term and the cursor style
+    fn refresh_restorer(&mut self) -> Result<()> {
+        if let Some(id) = self.restorer_id {
+            let state = self.restore_state();
+            if self.restorer_state.as_ref() != Some(&state) {
+                let (sequence, _) = self.restore_sequence()?;
+                update_restorer(id, sequence);
+                self.restorer_state = Some(state);
+            }
+        }
+        Ok(())
+    }
+
+    /// the state used by `write_restore` that might change while the term is running
+    fn restore_state(&self) -> RestoreState {
+        RestoreState {
+            cursor_style: self.screen.painted_cursor_style(),
+            clear_on_exit: self.clear_on_exit,
+            cursor_row: self.cursor_row,
+            height: self.screen.height(),
+            bottom_intact: self.bottom_intact,
+        }
+    }
+
+    /// the sequences that restore the terminal on exit and the fd to write them to
+    fn restore_sequence(&mut self) -> Result<(Vec<u8>, Option<RawFd>)> {
+        let mut output = self.output.take().ok_or(TuikitError::TerminalNotStarted)?;
+
+        // record the sequences instead of writing them out
+        output.flush();
+        self.write_restore(&mut output, true);
+        let sequence = output.take_buffer();
+        let fd = output.raw_fd();
+        self.output = Some(output);
+        Ok((sequence, fd))
+    }
+
     /// ensure the screen had enough height
     /// If the prefer height is full screen, it will enter alternate screen
     /// otherwise it will ensure there are enough lines at the bottom
@@ -767,6 +1999,7 @@ impl TermLock {
             self.alternate_screen = true;
             self.bottom_intact = false;
             self.cursor_row = 0;
+            self.normal_cursor_pos = cursor_pos;
             if !self.disable_alternate_screen {
                 output.enter_alternate_screen();
             }
@@ -804,7 +2037,13 @@ impl TermLock {
     }
 
     /// restart the terminal
-    pub fn restart(&mut self, output: Output, cursor_pos: (usize, usize)) -> Result<()> {
+    pub fn restart(&mut self, mut output: Output, cursor_pos: (usize, usize)) -> Result<()> {
+        if let Some(color_depth) = self.color_depth {
+            output.set_color_depth(color_depth);
+        }
+        self.screen.use_repeat(output.supports_repeat());
+        self.screen.use_erase_chars(output.supports_erase_chars());
+
         // ensure the output area had enough height
         self.output.replace(output);
         self.ensure_height(cursor_pos)?;
@@ -812,6 +2051,19 @@ impl TermLock {
         if self.mouse_enabled {
             self.enable_mouse()?;
         }
+        let output = self
+            .output
+            .as_mut()
+            .ok_or(TuikitError::TerminalNotStarted)?;
+        if self.bracketed_paste {
+            output.enable_bracketed_paste();
+        }
+        if self.focus_events {
+            output.enable_focus_events();
+        }
+        if !self.kitty_keyboard.is_empty() {
+            output.enable_kitty_keyboard(self.kitty_keyboard);
+        }
         Ok(())
     }
 
@@ -851,6 +2103,10 @@ impl TermLock {
         self.screen.show_cursor(show)
     }
 
+    pub fn set_cursor_style(&mut self, style: CursorStyle) -> Result<()> {
+        self.screen.set_cursor_style(style)
+    }
+
     /// Enable mouse support
     pub fn enable_mouse_support(&mut self) -> Result<()> {
         self.mouse_enabled = true;
@@ -863,6 +2119,25 @@ impl TermLock {
         self.disable_mouse()
     }
 
+    pub fn set_clipboard(&mut self, selection: ClipboardSelection, text: &str) -> Result<()> {
+        let output = self
+            .output
+            .as_mut()
+            .ok_or(TuikitError::TerminalNotStarted)?;
+        output.set_clipboard(selection, text);
+        output.flush();
+        Ok(())
+    }
+
+    pub fn request_clipboard(&mut self, selection: ClipboardSelection) -> Result<()> {
+        let output = self
+            .output
+            .as_mut()
+            .ok_or(TuikitError::TerminalNotStarted)?;
+        output.request_clipboard(selection);
+        Ok(())
+    }
+
     pub fn clear_on_exit(&mut self, clear: bool) {
         self.clear_on_exit = clear;
     }
@@ -873,7 +2148,7 @@ impl TermLock {
             .output
             .as_mut()
             .ok_or(TuikitError::TerminalNotStarted)?;
-        output.enable_mouse_support();
+        output.enable_mouse_tracking(self.mouse_tracking);
         Ok(())
     }
 
@@ -888,8 +2163,634 @@ impl TermLock {
     }
 }
 
+/// move the `CursorGoto` and `SetScrollRegion` commands down by `rows`
+fn offset_rows(commands: &mut [Command], rows: usize) {
+    for cmd in commands.iter_mut() {
+        match *cmd {
+            Command::CursorGoto { row, col } => {
+                *cmd = Command::CursorGoto {
+                    row: row + rows,
+                    col,
+                }
+            }
+            Command::SetScrollRegion { top, bottom } => {
+                *cmd = Command::SetScrollRegion {
+                    top: top + rows,
+                    bottom: bottom + rows,
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 impl Drop for TermLock {
     fn drop(&mut self) {
         let _ = self.pause(true);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn headless_term() -> Term {
+        let term = Term::with_options(TermOptions::default().headless(10, 3)).unwrap();
+        assert_eq!(Event::Restarted, term.poll_event().unwrap());
+        term
+    }
+
+    #[test]
+    fn test_headless_render() {
+        let term = headless_term();
+        assert_eq!((10, 3), term.term_size().unwrap());
+
+        term.print(1, 2, "hello").unwrap();
+        term.present().unwrap();
+        assert_eq!('h', term.rendered_cell(1, 2).unwrap().ch);
+        assert_eq!('o', term.rendered_cell(1, 6).unwrap().ch);
+        assert!(term.rendered_cell(3, 0).is_err());
+
+        let commands = term.take_commands();
+        assert!(commands
+            .iter()
+            .any(|cmd| matches!(cmd, Command::CursorGoto { row: 1, col: 2 })));
+        assert!(commands
+            .iter()
+            .any(|cmd| matches!(cmd, Command::PutChar('h'))));
+        assert!(term.take_commands().is_empty());
+    }
+
+    #[test]
+    fn test_headless_events() {
+        let term = headless_term();
+        term.send_event(Event::User(())).unwrap();
+        assert_eq!(Event::User(()), term.poll_event().unwrap());
+
+        term.feed_input(b"a").unwrap();
+        assert_eq!(Event::Key(Key::Char('a')), term.poll_event().unwrap());
+
+        term.pause().unwrap();
+        assert!(term.feed_input(b"a").is_err());
+    }
+
+    #[test]
+    fn test_synchronized_output() {
+        let options = TermOptions::default()
+            .headless(10, 3)
+            .synchronized_output(SynchronizedOutput::Enabled);
+        let term: Term = Term::with_options(options).unwrap();
+        term.print(0, 0, "hello").unwrap();
+        term.present().unwrap();
+
+        let commands = term.take_commands();
+        assert!(matches!(
+            commands.first(),
+            Some(Command::SynchronizedUpdate(true))
+        ));
+        assert!(matches!(
+            commands.last(),
+            Some(Command::SynchronizedUpdate(false))
+        ));
+
+        let term = headless_term();
+        term.present().unwrap();
+        assert!(!term
+            .take_commands()
+            .iter()
+            .any(|cmd| matches!(cmd, Command::SynchronizedUpdate(_))));
+    }
+
+    #[test]
+    fn test_color_depth() {
+        let color_depth = |term: &Term| {
+            let termlock = term.term_lock.lock();
+            termlock.output.as_ref().map(|output| output.color_depth())
+        };
+
+        let term = headless_term();
+        assert_eq!(Some(ColorDepth::Ansi256), color_depth(&term));
+
+        let options = TermOptions::default()
+            .headless(10, 3)
+            .color_depth(ColorDepth::TrueColor);
+        let term: Term = Term::with_options(options).unwrap();
+        assert_eq!(Some(ColorDepth::TrueColor), color_depth(&term));
+        term.pause().unwrap();
+        term.restart().unwrap();
+        assert_eq!(Some(ColorDepth::TrueColor), color_depth(&term));
+    }
+
+    #[cfg(feature = "event-stream")]
+    #[test]
+    fn test_event_stream() {
+        use futures_core::Stream;
+        use std::pin::Pin;
+
+        let term = Arc::new(headless_term());
+        let mut stream = term.event_stream();
+        let mut next_event = || {
+            futures_executor::block_on(std::future::poll_fn(|cx| {
+                Pin::new(&mut stream).poll_next(cx)
+            }))
+        };
+
+        term.feed_input(b"a").unwrap();
+        assert_eq!(Some(Event::Key(Key::Char('a'))), next_event());
+
+        let term_clone = term.clone();
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            term_clone.send_event(Event::User(())).unwrap();
+        });
+        assert_eq!(Some(Event::User(())), next_event());
+        sender.join().unwrap();
+    }
+
+    fn restarted(term: &Term) -> bool {
+        // pausing emits other events, e.g. the stopping size change listener emits `Resize`
+        while let Ok(event) = term.peek_event(WAIT_TIMEOUT) {
+            if event == Event::Restarted {
+                return true;
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn test_suspend() {
+        let term = headless_term();
+        term.suspend().unwrap();
+
+        // the term is restarted once it is continued
+        assert!(restarted(&term));
+        term.feed_input(b"a").unwrap();
+        assert_eq!(Event::Key(Key::Char('a')), term.poll_event().unwrap());
+
+        // continued by others
+        term.job_control.resume().unwrap();
+        assert!(restarted(&term));
+
+        // paused by the user
+        term.pause().unwrap();
+        term.job_control.resume().unwrap();
+        assert!(!restarted(&term));
+        assert!(term.feed_input(b"a").is_err());
+    }
+
+    #[test]
+    fn test_event_fd_drained_by_blocking_dequeue() {
+        use nix::poll::{poll, PollFd, PollFlags};
+
+        let term = headless_term();
+        term.send_event(Event::User(())).unwrap();
+        while term.poll_event().unwrap() != Event::User(()) {}
+
+        let mut fds = [PollFd::new(term.event_fd(), PollFlags::POLLIN)];
+        assert!(term.peek_event(Duration::from_millis(10)).is_err());
+        assert_eq!(0, poll(&mut fds, 0).unwrap());
+
+        term.send_event(Event::User(())).unwrap();
+        assert_eq!(1, poll(&mut fds, 0).unwrap());
+        assert_eq!(Event::User(()), term.poll_event().unwrap());
+    }
+
+    #[test]
+    fn test_job_control_without_polling() {
+        let term = headless_term();
+
+        // the terminal is given back and taken again by the job control listener itself, only
+        // `Event::Restarted` is left for the user
+        term.job_control.give_back().unwrap();
+        assert!(term.term_lock.lock().output.is_none());
+        term.job_control.take().unwrap();
+        assert!(term.term_lock.lock().output.is_some());
+        assert_eq!(Some(Event::Restarted), term.try_event());
+        assert_eq!(None, term.try_event());
+
+        // a paused term is left alone
+        term.pause().unwrap();
+        while term.try_event().is_some() {}
+        term.job_control.resume().unwrap();
+        assert!(term.term_lock.lock().output.is_none());
+        assert_eq!(None, term.try_event());
+    }
+
+    #[test]
+    fn test_mouse_motion_row_offset() {
+        use crate::key::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+
+        let options = TermOptions::default().headless(10, 5).raw_mouse(true);
+        let term: Term = Term::with_options(options).unwrap();
+        // an inline term starting from the 3rd row
+        term.term_lock.lock().cursor_row = 2;
+
+        // drag at the 5th row, move above the term is dropped, move at the 3rd row
+        term.feed_input(b"\x1b[<32;3;5M\x1b[<35;3;1M\x1b[<35;3;3M")
+            .unwrap();
+        // ctrl + drag at the 4th row
+        term.feed_input(b"\x1b[<48;3;4M").unwrap();
+
+        let mut keys = vec![];
+        while keys.len() < 3 {
+            if let Event::Key(key) = term.poll_event().unwrap() {
+                keys.push(key);
+            }
+        }
+        let event = MouseEvent::new(MouseEventKind::Drag(MouseButton::Left), 1, 2)
+            .modifiers(KeyModifiers::CTRL);
+        assert_eq!(
+            vec![
+                Key::MouseDrag(MouseButton::Left, 2, 2),
+                Key::MouseMove(0, 2),
+                Key::Mouse(event),
+            ],
+            keys
+        );
+    }
+
+    #[test]
+    fn test_insert_before() {
+        struct Lines(&'static [&'static str]);
+        impl Draw for Lines {
+            fn draw(&self, canvas: &mut dyn Canvas) -> crate::draw::DrawResult<()> {
+                for (row, line) in self.0.iter().enumerate() {
+                    canvas.print(row, 0, line)?;
+                }
+                Ok(())
+            }
+        }
+
+        let options = TermOptions::default()
+            .headless(10, 5)
+            .min_height(TermHeight::Fixed(2))
+            .height(TermHeight::Fixed(2));
+        let term: Term = Term::with_options(options).unwrap();
+        term.print(0, 0, "progress").unwrap();
+        term.present().unwrap();
+        term.take_commands();
+
+        // enough space below, the area is moved down
+        term.insert_before(2, &Lines(&["log1", "log2"])).unwrap();
+        let commands = term.take_commands();
+        assert!(commands
+            .iter()
+            .any(|cmd| matches!(cmd, Command::CursorGoto { row: 1, col: 0 })));
+        assert_eq!(2, term.term_lock.lock().get_term_start_row());
+        assert!(!term.term_lock.lock().bottom_intact);
+        // the area is redrawn
+        assert!(commands
+            .iter()
+            .skip_while(|cmd| !matches!(cmd, Command::CursorGoto { row: 2, col: 0 }))
+            .any(|cmd| matches!(cmd, Command::PutChar('p'))));
+        assert_eq!('p', term.rendered_cell(0, 0).unwrap().ch);
+
+        // the terminal is scrolled
+        term.insert_before(2, &Lines(&["log3", "log4"])).unwrap();
+        assert_eq!(3, term.term_lock.lock().get_term_start_row());
+        assert!(term.term_lock.lock().bottom_intact);
+    }
+
+    #[test]
+    fn test_set_height() {
+        let options = TermOptions::default()
+            .headless(10, 10)
+            .min_height(TermHeight::Fixed(1))
+            .height(TermHeight::Fixed(3));
+        let term: Term = Term::with_options(options).unwrap();
+        assert_eq!(Event::Restarted, term.poll_event().unwrap());
+        let resized = |term: &Term| loop {
+            match term.poll_event().unwrap() {
+                Event::Resize { width, height } => return (width, height),
+                _ => continue,
+            }
+        };
+
+        // grow & shrink
+        term.set_height(TermHeight::Fixed(5)).unwrap();
+        assert_eq!((10, 5), resized(&term));
+        term.set_height(TermHeight::Fixed(2)).unwrap();
+        assert_eq!((10, 2), resized(&term));
+        assert_eq!(0, term.term_lock.lock().get_term_start_row());
+
+        // scroll to reserve lines
+        struct Blank;
+        impl Draw for Blank {}
+        term.insert_before(7, &Blank).unwrap();
+        assert_eq!(7, term.term_lock.lock().get_term_start_row());
+        term.set_height(TermHeight::Fixed(5)).unwrap();
+        assert_eq!((10, 5), resized(&term));
+        assert_eq!(5, term.term_lock.lock().get_term_start_row());
+
+        // enter & leave fullscreen
+        term.set_max_height(TermHeight::Fixed(4)).unwrap();
+        assert_eq!((10, 4), resized(&term));
+        term.set_max_height(TermHeight::Percent(100)).unwrap();
+        assert_eq!((10, 5), resized(&term));
+        term.set_height(TermHeight::Percent(100)).unwrap();
+        assert_eq!((10, 10), resized(&term));
+        assert!(term.term_lock.lock().alternate_screen);
+        term.set_height(TermHeight::Fixed(3)).unwrap();
+        assert_eq!((10, 3), resized(&term));
+        assert!(!term.term_lock.lock().alternate_screen);
+        assert_eq!(5, term.term_lock.lock().get_term_start_row());
+    }
+
+    #[test]
+    fn test_query_default_colors() {
+        let term = Arc::new(headless_term());
+
+        let term_clone = term.clone();
+        let feeder = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            term_clone
+                .feed_input(b"\x1b]10;rgb:0000/0000/0000\x1b\\\x1b]11;rgb:ffff/ffff/ffff\x1b\\")
+                .unwrap();
+            term_clone.feed_input(b"\x1b[1;1Rx").unwrap();
+        });
+        assert_eq!(
+            (Color::Rgb(0, 0, 0), Color::Rgb(255, 255, 255)),
+            term.query_default_colors().unwrap()
+        );
+        feeder.join().unwrap();
+
+        // keys are still delivered as events
+        assert_eq!(
+            Event::Key(Key::Char('x')),
+            term.peek_event(WAIT_TIMEOUT).unwrap()
+        );
+
+        // no replies, fallback to default colors
+        assert_eq!(
+            vec![Color::Default, Color::Default],
+            term.query_palette_colors(&[1, 2]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_detect_capabilities() {
+        let options = TermOptions::default()
+            .headless(10, 3)
+            .synchronized_output(SynchronizedOutput::Detect);
+        let term: Arc<Term> = Arc::new(Term::with_options(options).unwrap());
+        assert!(term.capabilities().is_none());
+
+        let term_clone = term.clone();
+        let feeder = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            term_clone
+                .feed_input(b"\x1bP>|xterm(379)\x1b\\\x1b[?2026;2$y\x1b[?2004;0$y")
+                .unwrap();
+            term_clone.feed_input(b"\x1b[?64;1;4;22c\x1b[1;1R").unwrap();
+        });
+        let capabilities = term.detect_capabilities().unwrap();
+        feeder.join().unwrap();
+
+        assert_eq!(Some("xterm(379)"), capabilities.version.as_deref());
+        assert_eq!(64, capabilities.device_class);
+        assert_eq!(vec![1, 4, 22], capabilities.device_attributes);
+        assert!(capabilities.synchronized_output);
+        assert!(!capabilities.bracketed_paste);
+        assert!(!capabilities.focus_events);
+        assert!(!capabilities.kitty_keyboard);
+        assert!(capabilities.sixel);
+        assert_eq!(Some(capabilities), term.capabilities());
+
+        // synchronized output is detected as well
+        term.present().unwrap();
+        assert!(term
+            .take_commands()
+            .iter()
+            .any(|cmd| matches!(cmd, Command::SynchronizedUpdate(true))));
+    }
+
+    #[test]
+    fn test_bracketed_paste() {
+        let options = TermOptions::default().headless(10, 3).bracketed_paste(true);
+        let term: Term = Term::with_options(options).unwrap();
+        assert_eq!(Event::Restarted, term.poll_event().unwrap());
+
+        term.feed_input(b"\x1b[200~hello\nworld\x1b[201~").unwrap();
+        term.feed_input(b"x").unwrap();
+        assert_eq!(
+            Event::Paste("hello\nworld".to_string()),
+            term.peek_event(WAIT_TIMEOUT).unwrap()
+        );
+        assert_eq!(
+            Event::Key(Key::Char('x')),
+            term.peek_event(WAIT_TIMEOUT).unwrap()
+        );
+
+        // markers are passed through if not enabled
+        let term = headless_term();
+        term.feed_input(b"\x1b[200~a\x1b[201~").unwrap();
+        assert_eq!(
+            Event::Key(Key::BracketedPasteStart),
+            term.peek_event(WAIT_TIMEOUT).unwrap()
+        );
+        assert_eq!(
+            Event::Key(Key::Char('a')),
+            term.peek_event(WAIT_TIMEOUT).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_focus_events() {
+        let options = TermOptions::default().headless(10, 3).focus_events(true);
+        let term: Term = Term::with_options(options).unwrap();
+        assert_eq!(Event::Restarted, term.poll_event().unwrap());
+
+        term.feed_input(b"\x1b[O\x1b[I").unwrap();
+        assert_eq!(Event::FocusLost, term.peek_event(WAIT_TIMEOUT).unwrap());
+        assert_eq!(Event::FocusGained, term.peek_event(WAIT_TIMEOUT).unwrap());
+    }
+
+    #[test]
+    fn test_clipboard() {
+        let options = TermOptions::default().headless(10, 3);
+        let term: Term = Term::with_options(options).unwrap();
+        assert_eq!(Event::Restarted, term.poll_event().unwrap());
+
+        term.set_clipboard(ClipboardSelection::Clipboard, "hello")
+            .unwrap();
+        term.feed_input(b"\x1b]52;c;aGVsbG8=\x07").unwrap();
+        assert_eq!(
+            Event::Clipboard(ClipboardSelection::Clipboard, "hello".to_string()),
+            term.peek_event(WAIT_TIMEOUT).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cursor_style() {
+        use crate::output::CursorShape;
+
+        let has_style = |commands: Vec<Command>, style: CursorStyle| {
+            commands
+                .iter()
+                .any(|cmd| matches!(cmd, Command::CursorStyle(s) if *s == style))
+        };
+
+        let term = headless_term();
+        let bar = CursorStyle::new(CursorShape::Bar);
+        term.set_cursor_style(bar).unwrap();
+        term.present().unwrap();
+        assert!(has_style(term.take_commands(), bar));
+        term.present().unwrap();
+        assert!(!has_style(term.take_commands(), bar));
+
+        // the default style is restored on pause, so the style is painted again on restart
+        term.pause().unwrap();
+        term.restart().unwrap();
+        term.present().unwrap();
+        assert!(has_style(term.take_commands(), bar));
+    }
+
+    #[test]
+    fn test_request_redraw() {
+        let frames = |term: &Term| {
+            term.take_commands()
+                .iter()
+                .filter(|cmd| matches!(cmd, Command::CursorShow(false)))
+                .count()
+        };
+
+        let options = TermOptions::default().headless(10, 3).max_fps(5);
+        let term: Term = Term::with_options(options).unwrap();
+        assert_eq!(Event::Restarted, term.poll_event().unwrap());
+        term.present().unwrap();
+        assert_eq!(1, frames(&term));
+
+        // the requests are coalesced and presented in the next frame
+        for i in 0..10 {
+            term.print(0, 0, &i.to_string()).unwrap();
+            term.request_redraw().unwrap();
+        }
+        assert!(term.rendered_cell(0, 0).unwrap().is_empty());
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!('9', term.rendered_cell(0, 0).unwrap().ch);
+        assert_eq!(1, frames(&term));
+
+        // the pending request is fulfilled by an immediate present, present right before it so
+        // that the scheduler has to wait for the next frame rather than presenting right away.
+        term.present().unwrap();
+        term.print(0, 0, "a").unwrap();
+        term.request_redraw().unwrap();
+        term.present().unwrap();
+        assert_eq!('a', term.rendered_cell(0, 0).unwrap().ch);
+        thread::sleep(Duration::from_millis(400));
+        assert_eq!(2, frames(&term));
+    }
+
+    #[test]
+    fn test_next_tick() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(100);
+        // on time
+        assert_eq!(
+            start + 2 * interval,
+            next_tick(start + interval, interval, start + interval)
+        );
+        // a bit late, keep the pace
+        let late = start + interval + interval / 2;
+        assert_eq!(
+            start + 2 * interval,
+            next_tick(start + interval, interval, late)
+        );
+        // stalled for several intervals, do not fire right away
+        let stalled = start + 5 * interval + interval / 2;
+        assert_eq!(
+            stalled + interval,
+            next_tick(start + interval, interval, stalled)
+        );
+    }
+
+    #[test]
+    fn test_timers() {
+        let term = headless_term();
+        let timeout = Duration::from_millis(100);
+
+        term.add_timeout(Duration::from_millis(20), 1).unwrap();
+        term.add_timer(Duration::from_millis(30), 2).unwrap();
+        assert_eq!(Event::Tick(1), term.peek_event(WAIT_TIMEOUT).unwrap());
+        assert_eq!(Event::Tick(2), term.peek_event(WAIT_TIMEOUT).unwrap());
+        assert_eq!(Event::Tick(2), term.peek_event(WAIT_TIMEOUT).unwrap());
+
+        // timeouts fire only once, the timer keeps going till it is cancelled
+        term.cancel_timer(2).unwrap();
+        while let Ok(event) = term.peek_event(timeout) {
+            assert_eq!(Event::Tick(2), event); // emitted before the cancellation
+        }
+
+        // a timer with the same id is replaced
+        term.add_timeout(Duration::from_secs(60), 3).unwrap();
+        term.add_timeout(Duration::from_millis(10), 3).unwrap();
+        assert_eq!(Event::Tick(3), term.peek_event(WAIT_TIMEOUT).unwrap());
+        assert!(term.peek_event(timeout).is_err());
+    }
+
+    #[test]
+    fn test_restore_on_panic() {
+        install_restore_hooks();
+        let (rx, tx) = nix::unistd::pipe().unwrap();
+        let restorer = move || Restorer {
+            fd: tx,
+            sequence: b"x".to_vec(),
+            termios: None,
+            owner: thread::current().id(),
+        };
+
+        // only the terminals owned by the panicking thread are restored
+        let kept = register_restorer(restorer());
+        let result = thread::spawn(move || {
+            register_restorer(restorer());
+            panic!("the terminal owned by this thread is restored");
+        })
+        .join();
+        assert!(result.is_err());
+
+        let mut buf = [0; 8];
+        assert_eq!(1, nix::unistd::read(rx, &mut buf).unwrap());
+        assert!(unregister_restorer(kept).is_some());
+    }
+
+    #[test]
+    fn test_refresh_restorer() {
+        use crate::output::CursorShape;
+
+        let term = headless_term();
+        let id = register_restorer(Restorer {
+            fd: -1,
+            sequence: Vec::new(),
+            termios: None,
+            owner: thread::current().id(),
+        });
+        term.term_lock.lock().restorer_id = Some(id);
+        let sequence = |id| {
+            let restorer = unregister_restorer(id).unwrap();
+            let sequence = restorer.sequence.clone();
+            let id = register_restorer(restorer);
+            term.term_lock.lock().restorer_id = Some(id);
+            (id, sequence)
+        };
+
+        // rebuilt only if the state is changed
+        term.present().unwrap();
+        let (id, _) = sequence(id);
+        update_restorer(id, b"unchanged".to_vec());
+        term.present().unwrap();
+        let (id, unchanged) = sequence(id);
+        assert_eq!(b"unchanged".to_vec(), unchanged);
+
+        // the cursor style set later is restored as well
+        term.set_cursor_style(CursorStyle::new(CursorShape::Bar))
+            .unwrap();
+        term.present().unwrap();
+        let restore_style = b"\x1b[0 q";
+        let restorer = unregister_restorer(id).unwrap();
+        assert!(restorer
+            .sequence
+            .windows(restore_style.len())
+            .any(|window| window == restore_style));
+        term.term_lock.lock().restorer_id = None;
+    }
+}
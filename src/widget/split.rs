@@ -297,7 +297,7 @@ impl<'a, Message> Widget<Message> for HSplit<'a, Message> {
                 height,
             };
 
-            let mut sub_message = adjust_event(event, sub_rect)
+            let mut sub_message = adjust_event(event.clone(), sub_rect)
                 .map(|ev| split.as_ref().on_event(ev, sub_rect.adjust_origin()))
                 .unwrap_or_default();
             messages.append(&mut sub_message);
@@ -327,7 +327,7 @@ impl<'a, Message> Widget<Message> for HSplit<'a, Message> {
                 height,
             };
 
-            let mut sub_message = adjust_event(event, sub_rect)
+            let mut sub_message = adjust_event(event.clone(), sub_rect)
                 .map(|ev| split.as_mut().on_event_mut(ev, sub_rect.adjust_origin()))
                 .unwrap_or_default();
             messages.append(&mut sub_message);
@@ -501,7 +501,7 @@ impl<'a, Message> Widget<Message> for VSplit<'a, Message> {
                 width,
                 height: target_height,
             };
-            let mut sub_message = adjust_event(event, sub_rect)
+            let mut sub_message = adjust_event(event.clone(), sub_rect)
                 .map(|ev| split.as_ref().on_event(ev, sub_rect.adjust_origin()))
                 .unwrap_or_default();
             messages.append(&mut sub_message);
@@ -533,7 +533,7 @@ impl<'a, Message> Widget<Message> for VSplit<'a, Message> {
                 width,
                 height: target_height,
             };
-            let mut sub_message = adjust_event(event, sub_rect)
+            let mut sub_message = adjust_event(event.clone(), sub_rect)
                 .map(|ev| split.as_mut().on_event_mut(ev, sub_rect.adjust_origin()))
                 .unwrap_or_default();
             messages.append(&mut sub_message);
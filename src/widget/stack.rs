@@ -62,7 +62,7 @@ impl<'a, Message> Widget<Message> for Stack<'a, Message> {
     fn on_event(&self, event: Event, rect: Rectangle) -> Vec<Message> {
         // like javascript's capture, from top to bottom
         for widget in self.inner.iter().rev() {
-            let message = widget.on_event(event, rect);
+            let message = widget.on_event(event.clone(), rect);
             if !message.is_empty() {
                 return message;
             }
@@ -73,7 +73,7 @@ impl<'a, Message> Widget<Message> for Stack<'a, Message> {
     fn on_event_mut(&mut self, event: Event, rect: Rectangle) -> Vec<Message> {
         // like javascript's capture, from top to bottom
         for widget in self.inner.iter_mut().rev() {
-            let message = widget.on_event_mut(event, rect);
+            let message = widget.on_event_mut(event.clone(), rect);
             if !message.is_empty() {
                 return message;
             }